@@ -1,31 +1,116 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::clock::Slot;
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Slot,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
 
 mod create_market;
-pub use create_market::{create_market, create_market_instruction, SearchMarketAccount};
+pub(crate) use create_market::valid_tick_size;
+pub use create_market::{
+    create_market, create_market_instruction, find_market_address, find_vault_address,
+    MarketParams, MarketState, SearchMarketAccount, MAX_DECISION_SIGNERS, MAX_RANKED_RESULTS,
+};
 mod create_result;
-pub use create_result::{create_result, create_result_instruction, ResultAccount};
+pub use create_result::{
+    create_result, create_result_instruction, find_no_mint_address, find_result_address,
+    find_yes_mint_address, ResultAccount,
+};
 mod deposit;
+pub(crate) use deposit::PairMint;
 pub use deposit::{deposit, deposit_instruction};
 mod withdraw;
 pub use withdraw::{withdraw, withdraw_instruction};
+mod redeem;
+pub use redeem::{redeem, redeem_instruction};
+mod dispute;
+pub use dispute::{
+    challenge_decision, challenge_decision_instruction, finalize_market,
+    finalize_market_instruction, resolve_challenge, resolve_challenge_instruction,
+};
 mod decide;
-pub use decide::{decide, decide_instruction};
+pub(crate) use decide::record_decision;
+pub use decide::{decide, decide_instruction, decide_multisig_instruction};
+mod decide_weighted;
+pub use decide_weighted::{decide_weighted, decide_weighted_instruction, MAX_WEIGHTED_RESULTS};
+mod decide_ranked;
+pub use decide_ranked::{decide_ranked, decide_ranked_instruction};
+mod void_market;
+pub use void_market::{void_market, void_market_instruction};
+mod close_market;
+pub use close_market::{
+    close_market, close_market_instruction, close_result, close_result_instruction,
+};
+mod convert_no_to_yes;
+pub use convert_no_to_yes::{convert_no_to_yes, convert_no_to_yes_instruction};
+mod amm;
+pub use amm::{buy_from_amm, buy_from_amm_instruction, sell_to_amm, sell_to_amm_instruction};
+mod pool;
+pub use pool::{
+    create_pool, create_pool_instruction, find_lp_mint_address, find_pool_address,
+    find_pool_tokens_address, PoolAccount,
+};
+mod pool_liquidity;
+pub use pool_liquidity::{
+    add_liquidity, add_liquidity_instruction, remove_liquidity, remove_liquidity_instruction,
+};
+mod pool_swap;
+pub use pool_swap::{swap, swap_instruction, swap_output};
+mod events;
+pub use events::{SearchMarketEvent, EVENT_LOG_PREFIX, EVENT_VERSION};
+mod delegate_decision;
+pub use delegate_decision::{delegate_decision, delegate_decision_instruction};
 mod create_order;
-pub use create_order::{create_order, create_order_instruction, OrderAccount, OrderSide};
+pub use create_order::{
+    create_order, create_order_instruction, find_order_address, maker_account_metas, OrderAccount,
+    OrderSide,
+};
+pub(crate) use create_order::{
+    escrow_name, fee_vault_address, match_resting_orders, pair_mint_account_metas,
+};
 mod fill_order;
-pub use fill_order::{fill_order, fill_order_instruction};
+pub use fill_order::{fill_order, fill_order_instruction, FillSummary};
 mod cancel_order;
 pub use cancel_order::{cancel_order, cancel_order_instruction};
+mod expire_bounty;
+pub use expire_bounty::{expire_bounty, expire_bounty_instruction};
+mod claim_fees;
+pub use claim_fees::{
+    claim_fees, claim_fees_instruction, claim_protocol_fees, claim_protocol_fees_instruction,
+    find_fee_vault_address,
+};
+mod config;
+pub use config::{
+    accept_admin, accept_admin_instruction, find_config_address, find_program_data_address,
+    initialize_config, initialize_config_instruction, propose_admin, propose_admin_instruction,
+    set_paused, set_paused_instruction, update_config, update_config_instruction, ConfigAccount,
+    ConfigParams,
+};
+#[cfg(feature = "debug-invariants")]
+mod invariants;
+#[cfg(feature = "debug-invariants")]
+pub(crate) use invariants::{
+    check_order_escrow, check_pool_reserves, check_result_backing, check_settled_backing,
+};
+mod lmsr;
+mod order_book;
+pub use order_book::{find_book_address, BookEntry, OrderBookAccount, MAX_BOOK_ORDERS};
 
 #[cfg(test)]
 #[cfg(feature = "test-bpf")]
 pub mod test_utils {
+    pub use super::config::test::*;
     pub use super::create_market::test::*;
     pub use super::create_order::test::*;
     pub use super::create_result::test::*;
     pub use super::decide::test::*;
     pub use super::deposit::test::*;
+    pub use super::pool::test::*;
     pub use super::withdraw::test::*;
 }
 
@@ -34,19 +119,28 @@ pub enum AccountType {
     SearchMarket,
     Result,
     Order,
+    OrderBook,
+    Config,
+    Pool,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum SearchMarketInstruction {
     CreateMarket {
-        expires_slot_offset: u64,
         search_string: String,
+        nonce: u64,
+        market_bump_seed: u8,
+        vault_bump_seed: u8,
+        params: MarketParams,
     },
     CreateResult {
         url: String,
         name: String,
         snippet: String,
-        bump_seed: u8,
+        result_bump_seed: u8,
+        yes_mint_bump_seed: u8,
+        no_mint_bump_seed: u8,
+        book_bump_seed: u8,
     },
     Deposit {
         amount: u64,
@@ -59,11 +153,216 @@ pub enum SearchMarketInstruction {
         side: u8,
         price: u64,
         quantity: u64,
+        client_id: u64,
+        order_bump_seed: u8,
         escrow_bump_seed: u8,
+        referrer: Option<Pubkey>,
+        fee_vault_bump_seed: u8,
     },
     FillOrder {
-        sol_escrow_bump_seed: u8,
-        token_escrow_bump_seed: u8,
+        side: u8,
+        limit_price: u64,
+        quantity: u64,
+        referrer: Option<Pubkey>,
+        fee_vault_bump_seed: u8,
     },
     CancelOrder,
+    ExpireBounty,
+    ClaimFees {
+        fee_vault_bump_seed: u8,
+    },
+    InitializeConfig {
+        params: ConfigParams,
+        config_bump_seed: u8,
+    },
+    UpdateConfig {
+        params: ConfigParams,
+    },
+    SetPaused {
+        paused: bool,
+    },
+    ProposeAdmin {
+        new_admin: Pubkey,
+    },
+    AcceptAdmin,
+    ClaimProtocolFees {
+        fee_vault_bump_seed: u8,
+    },
+    Redeem {
+        amount: u64,
+    },
+    ChallengeDecision,
+    ResolveChallenge,
+    FinalizeMarket,
+    DelegateDecision {
+        delegate: Pubkey,
+        expires_slot: Slot,
+    },
+    DecideWeighted {
+        weights: Vec<u16>,
+    },
+    DecideRanked {
+        count: u8,
+    },
+    VoidMarket,
+    CloseResult,
+    CloseMarket,
+    ConvertNoToYes {
+        amount: u64,
+    },
+    BuyFromAmm {
+        yes: bool,
+        amount: u64,
+        max_cost: u64,
+    },
+    SellToAmm {
+        yes: bool,
+        amount: u64,
+        min_proceeds: u64,
+    },
+    CreatePool {
+        pool_bump_seed: u8,
+        pool_tokens_bump_seed: u8,
+        lp_mint_bump_seed: u8,
+    },
+    AddLiquidity {
+        yes_amount: u64,
+        max_sol: u64,
+    },
+    RemoveLiquidity {
+        lp_amount: u64,
+        min_yes: u64,
+        min_sol: u64,
+    },
+    Swap {
+        buy_yes: bool,
+        amount_in: u64,
+        min_out: u64,
+    },
+}
+
+/// Allocates `space` bytes owned by `owner` at the program address derived from `seeds`, with
+/// `payer` funding the rent-exempt minimum. Anyone can send lamports to an address before it is
+/// created, which `create_account` refuses, so a funded address is topped up, allocated and
+/// assigned instead.
+pub(crate) fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                required,
+                space as u64,
+                owner,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let top_up = required.saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, owner),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// Returns all of a program owned account's lamports to `destination` and clears its data so the
+/// runtime reclaims it at the end of the transaction.
+pub(crate) fn close_program_account(
+    account: &AccountInfo,
+    destination: &AccountInfo,
+) -> ProgramResult {
+    let lamports = destination
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(crate::SearchMarketError::ArithmeticOverflow)?;
+    **destination.try_borrow_mut_lamports()? = lamports;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
+
+/// The fee on `amount` lamports at `fee_bps`, rounded down.
+pub(crate) fn fee_amount(amount: u64, fee_bps: u16) -> Result<u64, crate::SearchMarketError> {
+    amount
+        .checked_mul(fee_bps as u64)
+        .map(|fee| fee / crate::BASIS_POINTS)
+        .ok_or(crate::SearchMarketError::ArithmeticOverflow)
+}
+
+/// Moves `amount` lamports out of a program owned account without closing it.
+pub(crate) fn transfer_program_lamports(
+    source: &AccountInfo,
+    destination: &AccountInfo,
+    amount: u64,
+) -> ProgramResult {
+    let source_lamports = source
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(crate::SearchMarketError::ArithmeticOverflow)?;
+    let destination_lamports = destination
+        .lamports()
+        .checked_add(amount)
+        .ok_or(crate::SearchMarketError::ArithmeticOverflow)?;
+    **source.try_borrow_mut_lamports()? = source_lamports;
+    **destination.try_borrow_mut_lamports()? = destination_lamports;
+    Ok(())
+}
+
+/// Returns whatever is left in an order's escrow to `sol_account`, then closes the escrow and the
+/// order itself.
+pub(crate) fn close_order<'a>(
+    side: OrderSide,
+    order: &AccountInfo<'a>,
+    escrow: &AccountInfo<'a>,
+    sol_account: &AccountInfo<'a>,
+    escrow_seeds: &[&[u8]],
+    spl_token_program: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    match side {
+        OrderSide::Buy | OrderSide::BuyNo => invoke_signed(
+            &system_instruction::transfer(escrow.key, sol_account.key, escrow.lamports()),
+            &[escrow.clone(), sol_account.clone(), system_program.clone()],
+            &[escrow_seeds],
+        )?,
+        OrderSide::Sell => invoke_signed(
+            &spl_token::instruction::close_account(
+                &spl_token::id(),
+                escrow.key,
+                sol_account.key,
+                escrow.key,
+                &[],
+            )?,
+            &[
+                escrow.clone(),
+                sol_account.clone(),
+                escrow.clone(),
+                spl_token_program.clone(),
+            ],
+            &[escrow_seeds],
+        )?,
+    }
+    close_program_account(order, sol_account)
 }
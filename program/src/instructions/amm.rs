@@ -0,0 +1,593 @@
+use super::{
+    find_config_address, find_no_mint_address, find_vault_address, find_yes_mint_address, lmsr,
+    ConfigAccount, MarketState, ResultAccount, SearchMarketAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+use std::convert::TryFrom;
+
+fn amm_account_metas(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    trader_pubkey: &Pubkey,
+    token_pubkey: &Pubkey,
+    yes: bool,
+) -> Vec<AccountMeta> {
+    let (vault_key, _bump_seed) = find_vault_address(program_id, market_pubkey);
+    let (mint_key, _mint_bump_seed) = if yes {
+        find_yes_mint_address(program_id, result_pubkey)
+    } else {
+        find_no_mint_address(program_id, result_pubkey)
+    };
+    vec![
+        AccountMeta::new_readonly(*market_pubkey, false),
+        AccountMeta::new(*result_pubkey, false),
+        AccountMeta::new(vault_key, false),
+        AccountMeta::new(*trader_pubkey, true),
+        AccountMeta::new(mint_key, false),
+        AccountMeta::new(*token_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ]
+}
+
+/// `yes` picks the result's yes or no tokens.
+#[allow(clippy::too_many_arguments)]
+pub fn buy_from_amm_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    token_pubkey: &Pubkey,
+    yes: bool,
+    amount: u64,
+    max_cost: u64,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::BuyFromAmm {
+        yes,
+        amount,
+        max_cost,
+    }
+    .try_to_vec()?;
+    let accounts = amm_account_metas(
+        program_id,
+        market_pubkey,
+        result_pubkey,
+        buyer_pubkey,
+        token_pubkey,
+        yes,
+    );
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// `yes` picks the result's yes or no tokens.
+#[allow(clippy::too_many_arguments)]
+pub fn sell_to_amm_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    token_owner_pubkey: &Pubkey,
+    token_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    yes: bool,
+    amount: u64,
+    min_proceeds: u64,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::SellToAmm {
+        yes,
+        amount,
+        min_proceeds,
+    }
+    .try_to_vec()?;
+    let mut accounts = amm_account_metas(
+        program_id,
+        market_pubkey,
+        result_pubkey,
+        token_owner_pubkey,
+        token_pubkey,
+        yes,
+    );
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+struct AmmAccounts<'a, 'b> {
+    market: &'a AccountInfo<'b>,
+    result: &'a AccountInfo<'b>,
+    vault: &'a AccountInfo<'b>,
+    trader: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    token: &'a AccountInfo<'b>,
+    spl_token_program: &'a AccountInfo<'b>,
+    system_program: &'a AccountInfo<'b>,
+}
+
+/// Checks the accounts shared by both trades and loads the market and the result, whose market
+/// maker must be funded and trading.
+fn load_amm<'a, 'b>(
+    program_id: &Pubkey,
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    yes: bool,
+) -> Result<(AmmAccounts<'a, 'b>, SearchMarketAccount, ResultAccount), ProgramError> {
+    let accounts = AmmAccounts {
+        market: next_account_info(account_info_iter)?,
+        result: next_account_info(account_info_iter)?,
+        vault: next_account_info(account_info_iter)?,
+        trader: next_account_info(account_info_iter)?,
+        mint: next_account_info(account_info_iter)?,
+        token: next_account_info(account_info_iter)?,
+        spl_token_program: next_account_info(account_info_iter)?,
+        system_program: next_account_info(account_info_iter)?,
+    };
+    let config_account_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    ConfigAccount::load_unpaused(program_id, config_account_info)?;
+
+    if *accounts.market.owner != *program_id || *accounts.result.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let market = SearchMarketAccount::try_from_slice(&accounts.market.data.borrow())?;
+    if market.state(clock.slot)? != MarketState::Open {
+        return Err(SearchMarketError::TradingClosed.into());
+    }
+    let result = ResultAccount::try_from_slice(&accounts.result.data.borrow())?;
+    if result.search_market != *accounts.market.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+    if result.amm_liquidity == 0 {
+        return Err(SearchMarketError::AmmNotFunded.into());
+    }
+
+    let mint = if yes { result.yes_mint } else { result.no_mint };
+    if mint != *accounts.mint.key {
+        return Err(SearchMarketError::MintMismatch.into());
+    }
+    if !accounts.trader.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *accounts.spl_token_program.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *accounts.system_program.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+    let vault_key = Pubkey::create_program_address(
+        &[
+            b"vault",
+            &accounts.market.key.to_bytes(),
+            &[market.vault_bump_seed],
+        ],
+        program_id,
+    )?;
+    if vault_key != *accounts.vault.key {
+        return Err(SearchMarketError::InvalidVault.into());
+    }
+    Ok((accounts, market, result))
+}
+
+/// The market maker's position after it sells `amount` more of the traded token, or buys some
+/// back when negative.
+fn moved_position(result: &ResultAccount, yes: bool, amount: i64) -> Option<(i64, i64)> {
+    if yes {
+        Some((result.amm_yes.checked_add(amount)?, result.amm_no))
+    } else {
+        Some((result.amm_yes, result.amm_no.checked_add(amount)?))
+    }
+}
+
+fn save_position(
+    result_account_info: &AccountInfo,
+    result: &mut ResultAccount,
+    position: (i64, i64),
+) -> ProgramResult {
+    result.amm_yes = position.0;
+    result.amm_no = position.1;
+    if let Some(price) = result.amm_yes_price() {
+        msg!("Market maker yes price {}", price);
+    }
+    result
+        .serialize(&mut &mut result_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Mints `amount` of the result's yes or no tokens to the buyer, who pays the market maker's
+/// price for them into the vault. The payment joins the result's collateral, which with the
+/// subsidy covers every token the maker has minted.
+pub fn buy_from_amm(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    yes: bool,
+    amount: u64,
+    max_cost: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let (accounts, market, mut result) = load_amm(program_id, account_info_iter, yes)?;
+
+    let signed_amount = i64::try_from(amount).map_err(|_| SearchMarketError::ArithmeticOverflow)?;
+    let position =
+        moved_position(&result, yes, signed_amount).ok_or(SearchMarketError::ArithmeticOverflow)?;
+    let cost = lmsr::buy_cost(
+        result.amm_liquidity,
+        result.amm_yes,
+        result.amm_no,
+        position.0,
+        position.1,
+    )
+    .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if cost > max_cost {
+        msg!("Cost {} above limit {}", cost, max_cost);
+        return Err(SearchMarketError::SlippageExceeded.into());
+    }
+
+    msg!("transfer sol buyer to vault");
+    invoke(
+        &system_instruction::transfer(accounts.trader.key, accounts.vault.key, cost),
+        &[
+            accounts.trader.clone(),
+            accounts.vault.clone(),
+            accounts.system_program.clone(),
+        ],
+    )?;
+
+    msg!("mint tokens to buyer");
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            accounts.spl_token_program.key,
+            accounts.mint.key,
+            accounts.token.key,
+            accounts.vault.key,
+            &[],
+            amount,
+        )?,
+        &[
+            accounts.mint.clone(),
+            accounts.token.clone(),
+            accounts.vault.clone(),
+            accounts.spl_token_program.clone(),
+        ],
+        &[&[
+            b"vault",
+            &accounts.market.key.to_bytes(),
+            &[market.vault_bump_seed],
+        ]],
+    )?;
+
+    result.collateral = result
+        .collateral
+        .checked_add(cost)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    save_position(accounts.result, &mut result, position)?;
+
+    SearchMarketEvent::BoughtFromAmm {
+        result: *accounts.result.key,
+        trader: *accounts.trader.key,
+        yes,
+        amount,
+        cost,
+    }
+    .emit()
+}
+
+/// Burns `amount` of the seller's yes or no tokens and pays the market maker's price for them out
+/// of the result's collateral. The maker buys any token of the result, not only ones it minted.
+pub fn sell_to_amm(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    yes: bool,
+    amount: u64,
+    min_proceeds: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let (accounts, market, mut result) = load_amm(program_id, account_info_iter, yes)?;
+    let destination_info = next_account_info(account_info_iter)?;
+
+    let signed_amount = i64::try_from(amount).map_err(|_| SearchMarketError::ArithmeticOverflow)?;
+    let position = moved_position(&result, yes, -signed_amount)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    let proceeds = lmsr::sell_proceeds(
+        result.amm_liquidity,
+        result.amm_yes,
+        result.amm_no,
+        position.0,
+        position.1,
+    )
+    .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if proceeds < min_proceeds {
+        msg!("Proceeds {} below limit {}", proceeds, min_proceeds);
+        return Err(SearchMarketError::SlippageExceeded.into());
+    }
+
+    msg!("burn tokens of seller");
+    invoke(
+        &spl_token::instruction::burn(
+            accounts.spl_token_program.key,
+            accounts.token.key,
+            accounts.mint.key,
+            accounts.trader.key,
+            &[],
+            amount,
+        )?,
+        &[
+            accounts.token.clone(),
+            accounts.mint.clone(),
+            accounts.trader.clone(),
+            accounts.spl_token_program.clone(),
+        ],
+    )?;
+
+    result.collateral = result
+        .collateral
+        .checked_sub(proceeds)
+        .ok_or(SearchMarketError::InsufficientCollateral)?;
+
+    msg!("transfer sol vault to seller");
+    invoke_signed(
+        &system_instruction::transfer(accounts.vault.key, destination_info.key, proceeds),
+        &[
+            accounts.vault.clone(),
+            destination_info.clone(),
+            accounts.system_program.clone(),
+        ],
+        &[&[
+            b"vault",
+            &accounts.market.key.to_bytes(),
+            &[market.vault_bump_seed],
+        ]],
+    )?;
+
+    save_position(accounts.result, &mut result, position)?;
+
+    SearchMarketEvent::SoldToAmm {
+        result: *accounts.result.key,
+        trader: *accounts.trader.key,
+        yes,
+        amount,
+        proceeds,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use crate::LAMPORTS_PER_TOKEN;
+    use solana_program::program_pack::Pack;
+    use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+    use spl_token::state::Account;
+
+    async fn process(
+        context: &mut ProgramTestContext,
+        instruction: Instruction,
+        signer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, signer], context.last_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    async fn load_result(context: &mut ProgramTestContext, result_key: Pubkey) -> ResultAccount {
+        let account = context
+            .banks_client
+            .get_account(result_key)
+            .await
+            .unwrap()
+            .unwrap();
+        ResultAccount::try_from_slice(&account.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_amm_trades() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let liquidity = 100;
+        let subsidy = lmsr::subsidy(liquidity).unwrap();
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.amm_liquidity = liquidity;
+        market.amm_subsidy = subsidy;
+        let (market_key, create_market) =
+            setup_market(&mut market, 10, &mut program_test, &program_id);
+
+        let mut instructions = vec![create_market];
+        let mut results = vec![];
+        for index in 0..2 {
+            let mut result = ResultAccount::new(
+                market_key,
+                format!("http://cyberpunk.net/{}", index),
+                String::from("Cyberpunk website"),
+                String::from("A game fated to be legend"),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            );
+            let (result_key, create_result) = setup_result(
+                &mut result,
+                index,
+                &decision_authority.pubkey(),
+                &program_id,
+            );
+            instructions.push(create_result);
+            results.push((result_key, result));
+        }
+
+        let trader = Keypair::new();
+        program_test.add_account(
+            trader.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let (funded_key, funded_result) = &results[0];
+        let (unfunded_key, unfunded_result) = &results[1];
+        let (yes_token, init_yes_token) =
+            setup_token(&funded_result.yes_mint, &trader.pubkey(), &mut program_test);
+        let (unfunded_yes_token, init_unfunded_yes_token) = setup_token(
+            &unfunded_result.yes_mint,
+            &trader.pubkey(),
+            &mut program_test,
+        );
+        instructions.extend([init_yes_token, init_unfunded_yes_token]);
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let result = load_result(&mut context, *funded_key).await;
+        assert_eq!(result.amm_liquidity, liquidity);
+        assert_eq!(result.collateral, subsidy);
+        assert_eq!(result.amm_yes_price(), Some(LAMPORTS_PER_TOKEN / 2));
+        let result = load_result(&mut context, *unfunded_key).await;
+        assert_eq!(result.amm_liquidity, 0);
+        assert_eq!(result.amm_yes_price(), None);
+
+        let cost = lmsr::buy_cost(liquidity, 0, 0, 10, 0).unwrap();
+        let buy = |max_cost| {
+            buy_from_amm_instruction(
+                &program_id,
+                &market_key,
+                funded_key,
+                &trader.pubkey(),
+                &yes_token,
+                true,
+                10,
+                max_cost,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            process(&mut context, buy(cost - 1), &trader)
+                .await
+                .unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::SlippageExceeded as u32)
+            )
+        );
+        process(&mut context, buy(cost), &trader).await.unwrap();
+
+        let unfunded_buy = buy_from_amm_instruction(
+            &program_id,
+            &market_key,
+            unfunded_key,
+            &trader.pubkey(),
+            &unfunded_yes_token,
+            true,
+            10,
+            LAMPORTS_PER_SOL,
+        )
+        .unwrap();
+        assert_eq!(
+            process(&mut context, unfunded_buy, &trader)
+                .await
+                .unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::AmmNotFunded as u32)
+            )
+        );
+
+        let result = load_result(&mut context, *funded_key).await;
+        assert_eq!((result.amm_yes, result.amm_no), (10, 0));
+        assert_eq!(result.collateral, subsidy + cost);
+        assert!(result.amm_yes_price().unwrap() > LAMPORTS_PER_TOKEN / 2);
+        let yes_account = context
+            .banks_client
+            .get_account(yes_token)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Account::unpack(&yes_account.data).unwrap().amount, 10);
+
+        let proceeds = lmsr::sell_proceeds(liquidity, 10, 0, 0, 0).unwrap();
+        assert!(proceeds <= cost);
+        let trader_before = context
+            .banks_client
+            .get_balance(trader.pubkey())
+            .await
+            .unwrap();
+        let sell = sell_to_amm_instruction(
+            &program_id,
+            &market_key,
+            funded_key,
+            &trader.pubkey(),
+            &yes_token,
+            &trader.pubkey(),
+            true,
+            10,
+            proceeds,
+        )
+        .unwrap();
+        process(&mut context, sell, &trader).await.unwrap();
+        let trader_after = context
+            .banks_client
+            .get_balance(trader.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(trader_after, trader_before + proceeds);
+
+        let result = load_result(&mut context, *funded_key).await;
+        assert_eq!((result.amm_yes, result.amm_no), (0, 0));
+        assert_eq!(result.collateral, subsidy + cost - proceeds);
+        let market_account = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let market = SearchMarketAccount::try_from_slice(&market_account.data).unwrap();
+        assert_eq!(market.amm_subsidy, 0);
+    }
+}
@@ -1,37 +1,41 @@
-use super::{OrderAccount, OrderSide, SearchMarketInstruction};
+use super::{
+    close_order, escrow_name, find_book_address, OrderAccount, OrderBookAccount, OrderSide,
+    SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::SearchMarketError;
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
+    msg,
     program::invoke_signed,
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction, system_program,
+    system_program,
 };
 
 pub fn cancel_order_instruction(
     program_id: &Pubkey,
     order: &Pubkey,
+    result: &Pubkey,
     side: OrderSide,
     sol_account: &Pubkey,
     token_account: &Pubkey,
-    execution_authority: &Pubkey,
+    authority: &Pubkey,
 ) -> Result<Instruction, std::io::Error> {
-    let escrow_name: &[u8] = match side {
-        OrderSide::Buy => b"sol_escrow",
-        OrderSide::Sell => b"token_escrow",
-    };
-    let (escrow_key, bump_seed) =
-        Pubkey::find_program_address(&[escrow_name, &order.to_bytes()], program_id);
+    let (escrow_key, _bump_seed) =
+        Pubkey::find_program_address(&[escrow_name(side), &order.to_bytes()], program_id);
+    let (book, _book_bump_seed) = find_book_address(program_id, result);
 
     let data = SearchMarketInstruction::CancelOrder.try_to_vec()?;
     let accounts = vec![
         AccountMeta::new(*order, false),
+        AccountMeta::new(book, false),
         AccountMeta::new(*sol_account, false),
         AccountMeta::new(*token_account, false),
         AccountMeta::new(escrow_key, false),
-        AccountMeta::new_readonly(*execution_authority, true),
+        AccountMeta::new_readonly(*authority, true),
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(system_program::id(), false),
     ];
@@ -45,34 +49,66 @@ pub fn cancel_order_instruction(
 pub fn cancel_order(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let order_account_info = next_account_info(account_info_iter)?;
+    let book_account_info = next_account_info(account_info_iter)?;
     let sol_account_info = next_account_info(account_info_iter)?;
     let token_account_info = next_account_info(account_info_iter)?;
     let escrow_account_info = next_account_info(account_info_iter)?;
-    let execution_authority_account_info = next_account_info(account_info_iter)?;
+    let authority_account_info = next_account_info(account_info_iter)?;
     let spl_token_program_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
 
-    let order = OrderAccount::try_from_slice(*order_account_info.data.borrow()).unwrap();
-    solana_program::msg!("{:?}", sol_account_info);
-    match order.side.into() {
-        OrderSide::Buy => invoke_signed(
-            &system_instruction::transfer(
-                escrow_account_info.key,
-                sol_account_info.key,
-                order.quantity * order.price,
-            ),
-            &[
-                escrow_account_info.clone(),
-                sol_account_info.clone(),
-                system_program_info.clone(),
-            ],
-            &[&[
-                b"sol_escrow",
-                &order_account_info.key.to_bytes(),
-                &[order.escrow_bump_seed],
-            ]],
-        )?,
-        OrderSide::Sell => invoke_signed(
+    if *order_account_info.owner != *program_id {
+        msg!("Order not owned by program");
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let order = OrderAccount::try_from_slice(*order_account_info.data.borrow())?;
+
+    if !authority_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *authority_account_info.key != order.owner
+        && *authority_account_info.key != order.execution_authority
+    {
+        msg!("Cancel not signed by order owner or execution authority");
+        return Err(SearchMarketError::WrongOrderAuthority.into());
+    }
+
+    if *sol_account_info.key != order.sol_account || *token_account_info.key != order.token_account
+    {
+        msg!("Refund accounts do not match order");
+        return Err(SearchMarketError::OrderAccountMismatch.into());
+    }
+
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let side: OrderSide = order.side.into();
+    let escrow_seeds: &[&[u8]] = &[
+        escrow_name(side),
+        &order_account_info.key.to_bytes(),
+        &[order.escrow_bump_seed],
+    ];
+    if Pubkey::create_program_address(escrow_seeds, program_id)? != *escrow_account_info.key {
+        return Err(SearchMarketError::EscrowMismatch.into());
+    }
+
+    if *book_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut book = OrderBookAccount::unpack(&book_account_info.data.borrow())?;
+    if book.result != order.result {
+        msg!("Book does not belong to order result");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    book.remove(side, order_account_info.key)?;
+    book.serialize(&mut &mut book_account_info.data.borrow_mut()[..])?;
+
+    if side == OrderSide::Sell {
+        invoke_signed(
             &spl_token::instruction::transfer(
                 &spl_token::id(),
                 &escrow_account_info.key,
@@ -88,15 +124,25 @@ pub fn cancel_order(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRes
                 escrow_account_info.clone(),
                 spl_token_program_info.clone(),
             ],
-            &[&[
-                b"token_escrow",
-                &order_account_info.key.to_bytes(),
-                &[order.escrow_bump_seed],
-            ]],
-        )?,
+            &[escrow_seeds],
+        )?;
     }
 
-    Ok(())
+    close_order(
+        side,
+        order_account_info,
+        escrow_account_info,
+        sol_account_info,
+        escrow_seeds,
+        spl_token_program_info,
+        system_program_info,
+    )?;
+
+    SearchMarketEvent::OrderCancelled {
+        result: order.result,
+        order: *order_account_info.key,
+    }
+    .emit()
 }
 
 #[cfg(test)]
@@ -106,15 +152,16 @@ pub mod test {
     use crate::instructions::test_utils::*;
     use crate::process_instruction;
     use crate::test_utils::*;
-    use crate::{ResultAccount, SearchMarketAccount};
+    use crate::{fill_order_instruction, ResultAccount, SearchMarketAccount};
     use solana_program::program_pack::Pack;
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::account::ReadableAccount;
     use solana_sdk::{
         account::Account as SolanaAccount,
+        instruction::InstructionError,
         rent::Rent,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
 
     #[tokio::test]
@@ -124,9 +171,15 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -135,9 +188,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let deposit_keypair = Keypair::new();
         let (yes_token_pubkey, init_yes_token) = setup_token(
@@ -165,16 +218,11 @@ pub mod test {
         );
 
         let sol_account_keypair = Keypair::new();
-        let sol_account = SolanaAccount::new(
-            Rent::default().minimum_balance(spl_token::state::Account::LEN) + 500 * 100,
-            0,
-            &system_program::id(),
-        );
-        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
         let mut order = OrderAccount::new(
             market_key,
             result_key,
             sol_account_keypair.pubkey(),
+            sol_account_keypair.pubkey(),
             yes_token_pubkey,
             OrderSide::Sell,
             500,
@@ -187,13 +235,22 @@ pub mod test {
             &mut order,
             &result.yes_mint,
             &deposit_keypair.pubkey(),
-            &mut program_test,
             &program_id,
         );
 
+        let sol_account = SolanaAccount::new(
+            Rent::default().minimum_balance(spl_token::state::Account::LEN)
+                + 500 * 100
+                + minimum_balance(&order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
         let cancel_order = cancel_order_instruction(
             &program_id,
             &order_key,
+            &result_key,
             order.side.into(),
             &order.sol_account,
             &order.token_account,
@@ -236,6 +293,26 @@ pub mod test {
             .unwrap();
         let yes_token = spl_token::state::Account::unpack(&yes_token_account.data()).unwrap();
         assert_eq!(yes_token.amount, 100);
+
+        assert_eq!(banks_client.get_account(escrow_key).await.unwrap(), None);
+        assert_eq!(banks_client.get_account(order_key).await.unwrap(), None);
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.entries(order.side.into()), &vec![]);
+
+        let sol_account = banks_client
+            .get_account(order.sol_account)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            sol_account.lamports,
+            Rent::default().minimum_balance(spl_token::state::Account::LEN)
+                + 500 * 100
+                + minimum_balance(&order).unwrap()
+        );
     }
 
     #[tokio::test]
@@ -245,9 +322,15 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -256,9 +339,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let deposit_keypair = Keypair::new();
         let (yes_token_pubkey, init_yes_token) = setup_token(
@@ -286,17 +369,158 @@ pub mod test {
         );
 
         let sol_account_keypair = Keypair::new();
+        let mut order = OrderAccount::new(
+            market_key,
+            result_key,
+            sol_account_keypair.pubkey(),
+            sol_account_keypair.pubkey(),
+            yes_token_pubkey,
+            OrderSide::Buy,
+            500,
+            100,
+            0,
+            1,
+            deposit_keypair.pubkey(),
+        );
+        let (order_key, escrow_key, create_order) = setup_order(
+            &mut order,
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &program_id,
+        );
+
         let sol_account = SolanaAccount::new(
-            2 * Rent::default().minimum_balance(0) + 500 * 100,
+            2 * Rent::default().minimum_balance(0) + 500 * 100 + minimum_balance(&order).unwrap(),
             0,
             &system_program::id(),
         );
         program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
+        let cancel_order = cancel_order_instruction(
+            &program_id,
+            &order_key,
+            &result_key,
+            order.side.into(),
+            &order.sol_account,
+            &order.token_account,
+            &order.execution_authority,
+        )
+        .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut setup_transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                deposit_instruction,
+            ],
+            Some(&payer.pubkey()),
+        );
+        setup_transaction.sign(
+            &[&payer, &decision_authority, &deposit_keypair],
+            recent_blockhash,
+        );
+        banks_client
+            .process_transaction(setup_transaction)
+            .await
+            .unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[create_order, cancel_order], Some(&payer.pubkey()));
+        transaction.sign(
+            &[&payer, &deposit_keypair, &sol_account_keypair],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert_eq!(banks_client.get_account(escrow_key).await.unwrap(), None);
+        assert_eq!(banks_client.get_account(order_key).await.unwrap(), None);
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.entries(order.side.into()), &vec![]);
+
+        let sol_account = banks_client
+            .get_account(order.sol_account)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            sol_account.lamports,
+            2 * Rent::default().minimum_balance(0)
+                + order.quantity * order.price
+                + minimum_balance(&order).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order_after_partial_fill() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let sol_account_keypair = Keypair::new();
+        let (bid_token_pubkey, init_bid_token) = setup_token(
+            &result.yes_mint,
+            &sol_account_keypair.pubkey(),
+            &mut program_test,
+        );
         let mut order = OrderAccount::new(
             market_key,
             result_key,
             sol_account_keypair.pubkey(),
-            yes_token_pubkey,
+            sol_account_keypair.pubkey(),
+            bid_token_pubkey,
             OrderSide::Buy,
             500,
             100,
@@ -308,13 +532,35 @@ pub mod test {
             &mut order,
             &result.yes_mint,
             &deposit_keypair.pubkey(),
-            &mut program_test,
             &program_id,
         );
 
+        let sol_account = SolanaAccount::new(
+            Rent::default().minimum_balance(0) + 500 * 100 + minimum_balance(&order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
+        let sell_into_bid = fill_order_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &deposit_keypair.pubkey(),
+            &yes_token_pubkey,
+            &deposit_keypair.pubkey(),
+            None,
+            OrderSide::Sell,
+            400,
+            40,
+            &[(order_key, order.clone())],
+        )
+        .unwrap();
+
         let cancel_order = cancel_order_instruction(
             &program_id,
             &order_key,
+            &result_key,
             order.side.into(),
             &order.sol_account,
             &order.token_account,
@@ -329,6 +575,7 @@ pub mod test {
                 create_result,
                 init_yes_token,
                 init_no_token,
+                init_bid_token,
                 deposit_instruction,
             ],
             Some(&payer.pubkey()),
@@ -343,7 +590,7 @@ pub mod test {
             .unwrap();
 
         let mut transaction =
-            Transaction::new_with_payer(&[create_order, cancel_order], Some(&payer.pubkey()));
+            Transaction::new_with_payer(&[create_order, sell_into_bid], Some(&payer.pubkey()));
         transaction.sign(
             &[&payer, &deposit_keypair, &sol_account_keypair],
             recent_blockhash,
@@ -351,7 +598,25 @@ pub mod test {
         banks_client.process_transaction(transaction).await.unwrap();
 
         let escrow_account = banks_client.get_account(escrow_key).await.unwrap().unwrap();
-        assert_eq!(escrow_account.lamports, Rent::default().minimum_balance(0));
+        assert_eq!(
+            escrow_account.lamports,
+            500 * 60 + Rent::default().minimum_balance(0)
+        );
+
+        let mut transaction = Transaction::new_with_payer(&[cancel_order], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &deposit_keypair], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert_eq!(banks_client.get_account(escrow_key).await.unwrap(), None);
+        assert_eq!(banks_client.get_account(order_key).await.unwrap(), None);
+
+        let bid_token_account = banks_client
+            .get_account(bid_token_pubkey)
+            .await
+            .unwrap()
+            .unwrap();
+        let bid_token = spl_token::state::Account::unpack(bid_token_account.data()).unwrap();
+        assert_eq!(bid_token.amount, 40);
 
         let sol_account = banks_client
             .get_account(order.sol_account)
@@ -360,7 +625,145 @@ pub mod test {
             .unwrap();
         assert_eq!(
             sol_account.lamports,
-            Rent::default().minimum_balance(0) + order.quantity * order.price
+            500 * 60 + Rent::default().minimum_balance(0) + minimum_balance(&order).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order_wrong_authority() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let sol_account_keypair = Keypair::new();
+        let mut order = OrderAccount::new(
+            market_key,
+            result_key,
+            sol_account_keypair.pubkey(),
+            sol_account_keypair.pubkey(),
+            yes_token_pubkey,
+            OrderSide::Buy,
+            500,
+            100,
+            0,
+            1,
+            deposit_keypair.pubkey(),
+        );
+        let (order_key, escrow_key, create_order) = setup_order(
+            &mut order,
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &program_id,
+        );
+
+        let sol_account = SolanaAccount::new(
+            2 * Rent::default().minimum_balance(0) + 500 * 100 + minimum_balance(&order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
+        let other_authority = Keypair::new();
+        let cancel_order = cancel_order_instruction(
+            &program_id,
+            &order_key,
+            &result_key,
+            order.side.into(),
+            &order.sol_account,
+            &order.token_account,
+            &other_authority.pubkey(),
+        )
+        .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut setup_transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                deposit_instruction,
+            ],
+            Some(&payer.pubkey()),
+        );
+        setup_transaction.sign(
+            &[&payer, &decision_authority, &deposit_keypair],
+            recent_blockhash,
+        );
+        banks_client
+            .process_transaction(setup_transaction)
+            .await
+            .unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[create_order, cancel_order], Some(&payer.pubkey()));
+        transaction.sign(
+            &[
+                &payer,
+                &deposit_keypair,
+                &sol_account_keypair,
+                &other_authority,
+            ],
+            recent_blockhash,
+        );
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(SearchMarketError::WrongOrderAuthority as u32)
+            )
         );
+        assert_eq!(banks_client.get_account(escrow_key).await.unwrap(), None);
     }
 }
@@ -0,0 +1,243 @@
+use super::{find_config_address, ConfigAccount, SearchMarketEvent, SearchMarketInstruction};
+use crate::SearchMarketError;
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+
+/// Fees owed to a frontend accrue as lamports on this address until the frontend claims them.
+/// Fees from orders without a referrer accrue to the vault of the default pubkey, which the config
+/// admin claims with `claim_protocol_fees`.
+pub fn find_fee_vault_address(program_id: &Pubkey, referrer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"fees", &referrer.to_bytes()], program_id)
+}
+
+pub fn claim_fees_instruction(
+    program_id: &Pubkey,
+    referrer: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    let (fee_vault, fee_vault_bump_seed) = find_fee_vault_address(program_id, referrer);
+    let data = SearchMarketInstruction::ClaimFees {
+        fee_vault_bump_seed,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(fee_vault, false),
+        AccountMeta::new(*referrer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn claim_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_vault_bump_seed: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let fee_vault_account_info = next_account_info(account_info_iter)?;
+    let referrer_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !referrer_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    sweep_fee_vault(
+        program_id,
+        referrer_account_info.key,
+        fee_vault_bump_seed,
+        fee_vault_account_info,
+        referrer_account_info,
+        system_program_info,
+    )
+}
+
+pub fn claim_protocol_fees_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    let (config, _config_bump_seed) = find_config_address(program_id);
+    let (fee_vault, fee_vault_bump_seed) = find_fee_vault_address(program_id, &Pubkey::default());
+    let data = SearchMarketInstruction::ClaimProtocolFees {
+        fee_vault_bump_seed,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(fee_vault, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn claim_protocol_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_vault_bump_seed: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let fee_vault_account_info = next_account_info(account_info_iter)?;
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let config = ConfigAccount::load(program_id, config_account_info)?;
+    if !admin_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.admin != *admin_account_info.key {
+        return Err(SearchMarketError::WrongAdmin.into());
+    }
+
+    sweep_fee_vault(
+        program_id,
+        &Pubkey::default(),
+        fee_vault_bump_seed,
+        fee_vault_account_info,
+        admin_account_info,
+        system_program_info,
+    )
+}
+
+fn sweep_fee_vault<'a>(
+    program_id: &Pubkey,
+    referrer: &Pubkey,
+    fee_vault_bump_seed: u8,
+    fee_vault_account_info: &AccountInfo<'a>,
+    destination_account_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let fee_vault_seeds: &[&[u8]] = &[b"fees", &referrer.to_bytes(), &[fee_vault_bump_seed]];
+    if Pubkey::create_program_address(fee_vault_seeds, program_id)? != *fee_vault_account_info.key {
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    let amount = fee_vault_account_info.lamports();
+    msg!("Claiming {} lamports", amount);
+    invoke_signed(
+        &system_instruction::transfer(
+            fee_vault_account_info.key,
+            destination_account_info.key,
+            amount,
+        ),
+        &[
+            fee_vault_account_info.clone(),
+            destination_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[fee_vault_seeds],
+    )?;
+
+    SearchMarketEvent::FeesClaimed {
+        referrer: *referrer,
+        amount,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::setup_config;
+    use crate::process_instruction;
+    use solana_program_test::{processor, ProgramTest};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+
+    #[tokio::test]
+    async fn test_claim_fees() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let frontend = Keypair::new();
+        program_test.add_account(
+            frontend.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let (fee_vault_key, _fee_vault_bump_seed) =
+            find_fee_vault_address(&program_id, &frontend.pubkey());
+        program_test.add_account(
+            fee_vault_key,
+            SolanaAccount::new(5_000, 0, &system_program::id()),
+        );
+
+        let claim_fees = claim_fees_instruction(&program_id, &frontend.pubkey()).unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction = Transaction::new_with_payer(&[claim_fees], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &frontend], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert_eq!(banks_client.get_account(fee_vault_key).await.unwrap(), None);
+        let frontend_account = banks_client
+            .get_account(frontend.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frontend_account.lamports, LAMPORTS_PER_SOL + 5_000);
+    }
+
+    #[tokio::test]
+    async fn test_claim_protocol_fees() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let admin = Keypair::new();
+        setup_config(&mut program_test, &program_id, &admin.pubkey());
+        program_test.add_account(
+            admin.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let (fee_vault_key, _fee_vault_bump_seed) =
+            find_fee_vault_address(&program_id, &Pubkey::default());
+        program_test.add_account(
+            fee_vault_key,
+            SolanaAccount::new(5_000, 0, &system_program::id()),
+        );
+
+        let claim_protocol_fees =
+            claim_protocol_fees_instruction(&program_id, &admin.pubkey()).unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction =
+            Transaction::new_with_payer(&[claim_protocol_fees], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert_eq!(banks_client.get_account(fee_vault_key).await.unwrap(), None);
+        let admin_account = banks_client
+            .get_account(admin.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(admin_account.lamports, LAMPORTS_PER_SOL + 5_000);
+    }
+}
@@ -0,0 +1,819 @@
+use super::{
+    close_order, close_program_account, escrow_name, find_book_address, find_config_address,
+    find_vault_address, transfer_program_lamports, ConfigAccount, OrderAccount, OrderBookAccount,
+    OrderSide, ResultAccount, SearchMarketAccount, SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke_signed,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+use spl_token::state::{Account, Mint};
+
+pub fn close_result_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    result: &ResultAccount,
+    fee_destination_pubkey: &Pubkey,
+    resting_orders: &[(Pubkey, OrderAccount)],
+) -> Result<Instruction, std::io::Error> {
+    let (book_key, _book_bump_seed) = find_book_address(program_id, result_pubkey);
+    let (vault_key, _vault_bump_seed) = find_vault_address(program_id, market_pubkey);
+    let data = SearchMarketInstruction::CloseResult.try_to_vec()?;
+    let mut accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(*result_pubkey, false),
+        AccountMeta::new(book_key, false),
+        AccountMeta::new(result.yes_mint, false),
+        AccountMeta::new_readonly(result.no_mint, false),
+        AccountMeta::new(vault_key, false),
+        AccountMeta::new(*fee_destination_pubkey, false),
+        AccountMeta::new(result.payer, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for (order_key, order) in resting_orders {
+        let (escrow_key, _bump_seed) = Pubkey::find_program_address(
+            &[escrow_name(order.side.into()), &order_key.to_bytes()],
+            program_id,
+        );
+        accounts.push(AccountMeta::new(*order_key, false));
+        accounts.push(AccountMeta::new(escrow_key, false));
+        accounts.push(AccountMeta::new(order.sol_account, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Closes a settled market's result and its order book, returning their rent to the result's
+/// payer. Every token that still redeems for something must have been redeemed, unless the
+/// config's grace period has run since settlement; the collateral left behind then goes to the
+/// fee destination. The mints stay, as the token program cannot close them. Anyone may crank this.
+///
+/// Orders still resting once the grace period has run are passed after the fixed accounts as
+/// (order, escrow, sol account) triples and closed, refunding their escrow to the sol account.
+/// The yes tokens of a resting sell are burned, their collateral being swept with the rest.
+pub fn close_result(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let result_account_info = next_account_info(account_info_iter)?;
+    let book_account_info = next_account_info(account_info_iter)?;
+    let yes_mint_account_info = next_account_info(account_info_iter)?;
+    let no_mint_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    let config = ConfigAccount::load(program_id, config_account_info)?;
+
+    if *market_account_info.owner != *program_id || *result_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+    let result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.search_market != *market_account_info.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+
+    if !market.is_settled() {
+        return Err(SearchMarketError::MarketNotFinalized.into());
+    }
+
+    if result.payer != *payer_account_info.key {
+        return Err(SearchMarketError::PayerMismatch.into());
+    }
+    if market.fee_destination != *fee_destination_info.key {
+        return Err(SearchMarketError::FeeDestinationMismatch.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+
+    let vault_seeds: &[&[u8]] = &[
+        b"vault",
+        &market_account_info.key.to_bytes(),
+        &[market.vault_bump_seed],
+    ];
+    if Pubkey::create_program_address(vault_seeds, program_id)? != *vault_account_info.key {
+        return Err(SearchMarketError::InvalidVault.into());
+    }
+
+    if result.yes_mint != *yes_mint_account_info.key || result.no_mint != *no_mint_account_info.key
+    {
+        return Err(SearchMarketError::MintMismatch.into());
+    }
+
+    let grace_end = market
+        .settled_slot
+        .saturating_add(config.params.close_grace_period);
+
+    if *book_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut book = OrderBookAccount::unpack(&book_account_info.data.borrow())?;
+    if book.result != *result_account_info.key {
+        msg!("Book does not belong to result");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    if clock.slot > grace_end {
+        while let Some(order_account_info) = account_info_iter.next() {
+            let escrow_account_info = next_account_info(account_info_iter)?;
+            let sol_account_info = next_account_info(account_info_iter)?;
+            refund_resting_order(
+                program_id,
+                &mut book,
+                order_account_info,
+                escrow_account_info,
+                sol_account_info,
+                yes_mint_account_info,
+                spl_token_program_info,
+                system_program_info,
+            )?;
+        }
+    }
+    if !book.bids.is_empty() || !book.asks.is_empty() {
+        return Err(SearchMarketError::OrdersResting.into());
+    }
+    let (yes_value, no_value) = market.redemption_values(result_account_info.key, &result);
+    let mut outstanding: u64 = 0;
+    for (mint_account_info, value) in [
+        (yes_mint_account_info, yes_value),
+        (no_mint_account_info, no_value),
+    ] {
+        if value > 0 {
+            outstanding = outstanding
+                .checked_add(Mint::unpack(&mint_account_info.data.borrow())?.supply)
+                .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        }
+    }
+    if outstanding > 0 && clock.slot <= grace_end {
+        msg!(
+            "{} tokens outstanding until slot {}",
+            outstanding,
+            grace_end
+        );
+        return Err(SearchMarketError::TokensOutstanding.into());
+    }
+
+    if result.collateral > 0 {
+        msg!("Sweeping {} unredeemed collateral", result.collateral);
+        invoke_signed(
+            &system_instruction::transfer(
+                vault_account_info.key,
+                fee_destination_info.key,
+                result.collateral,
+            ),
+            &[
+                vault_account_info.clone(),
+                fee_destination_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+    }
+
+    close_program_account(book_account_info, payer_account_info)?;
+    close_program_account(result_account_info, payer_account_info)?;
+
+    market.closed_results = market
+        .closed_results
+        .checked_add(1)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::ResultClosed {
+        market: *market_account_info.key,
+        result: *result_account_info.key,
+        swept: result.collateral,
+    }
+    .emit()
+}
+
+/// Closes an order left resting on a settled result's book, as `cancel_order` would for its owner.
+#[allow(clippy::too_many_arguments)]
+fn refund_resting_order<'a>(
+    program_id: &Pubkey,
+    book: &mut OrderBookAccount,
+    order_account_info: &AccountInfo<'a>,
+    escrow_account_info: &AccountInfo<'a>,
+    sol_account_info: &AccountInfo<'a>,
+    yes_mint_account_info: &AccountInfo<'a>,
+    spl_token_program_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    if *order_account_info.owner != *program_id {
+        msg!("Order not owned by program");
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let order = OrderAccount::try_from_slice(*order_account_info.data.borrow())?;
+    if order.result != book.result {
+        msg!("Order does not belong to result");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    if *sol_account_info.key != order.sol_account {
+        msg!("Refund account does not match order");
+        return Err(SearchMarketError::OrderAccountMismatch.into());
+    }
+
+    let side: OrderSide = order.side.into();
+    let escrow_seeds: &[&[u8]] = &[
+        escrow_name(side),
+        &order_account_info.key.to_bytes(),
+        &[order.escrow_bump_seed],
+    ];
+    if Pubkey::create_program_address(escrow_seeds, program_id)? != *escrow_account_info.key {
+        return Err(SearchMarketError::EscrowMismatch.into());
+    }
+    book.remove(side, order_account_info.key)?;
+
+    if side == OrderSide::Sell {
+        let escrowed = Account::unpack(&escrow_account_info.data.borrow())?.amount;
+        invoke_signed(
+            &spl_token::instruction::burn(
+                &spl_token::id(),
+                escrow_account_info.key,
+                yes_mint_account_info.key,
+                escrow_account_info.key,
+                &[],
+                escrowed,
+            )?,
+            &[
+                escrow_account_info.clone(),
+                yes_mint_account_info.clone(),
+                escrow_account_info.clone(),
+                spl_token_program_info.clone(),
+            ],
+            &[escrow_seeds],
+        )?;
+    }
+    close_order(
+        side,
+        order_account_info,
+        escrow_account_info,
+        sol_account_info,
+        escrow_seeds,
+        spl_token_program_info,
+        system_program_info,
+    )?;
+
+    SearchMarketEvent::OrderCancelled {
+        result: order.result,
+        order: *order_account_info.key,
+    }
+    .emit()
+}
+
+pub fn close_market_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    market: &SearchMarketAccount,
+) -> Result<Instruction, std::io::Error> {
+    let (vault_key, _vault_bump_seed) = find_vault_address(program_id, market_pubkey);
+    let data = SearchMarketInstruction::CloseMarket.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(vault_key, false),
+        AccountMeta::new(market.fee_destination, false),
+        AccountMeta::new(market.payer, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Closes a settled market once all of its results are closed, returning its rent to the payer.
+/// A bounty nobody earned goes to the fee destination, as `expire_bounty` would send it, along with
+/// whatever converted-set collateral was never redeemed. Market maker subsidy no result drew on is
+/// refunded to the payer, who put it up.
+pub fn close_market(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+
+    if !market.is_settled() {
+        return Err(SearchMarketError::MarketNotFinalized.into());
+    }
+    if market.closed_results != market.result_count {
+        msg!(
+            "{} of {} results closed",
+            market.closed_results,
+            market.result_count
+        );
+        return Err(SearchMarketError::ResultsStillOpen.into());
+    }
+
+    if market.payer != *payer_account_info.key {
+        return Err(SearchMarketError::PayerMismatch.into());
+    }
+    if market.fee_destination != *fee_destination_info.key {
+        return Err(SearchMarketError::FeeDestinationMismatch.into());
+    }
+
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let vault_seeds: &[&[u8]] = &[
+        b"vault",
+        &market_account_info.key.to_bytes(),
+        &[market.vault_bump_seed],
+    ];
+    if Pubkey::create_program_address(vault_seeds, program_id)? != *vault_account_info.key {
+        return Err(SearchMarketError::InvalidVault.into());
+    }
+
+    if market.pooled_collateral > 0 {
+        msg!("Sweeping {} pooled collateral", market.pooled_collateral);
+        invoke_signed(
+            &system_instruction::transfer(
+                vault_account_info.key,
+                fee_destination_info.key,
+                market.pooled_collateral,
+            ),
+            &[
+                vault_account_info.clone(),
+                fee_destination_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+    }
+    if market.amm_subsidy > 0 {
+        msg!("Refunding {} market maker subsidy", market.amm_subsidy);
+        invoke_signed(
+            &system_instruction::transfer(
+                vault_account_info.key,
+                payer_account_info.key,
+                market.amm_subsidy,
+            ),
+            &[
+                vault_account_info.clone(),
+                payer_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+    }
+    // What is left is the vault's rent-exempt reserve and any rounding dust.
+    let vault_remainder = vault_account_info.lamports();
+    if vault_remainder > 0 {
+        invoke_signed(
+            &system_instruction::transfer(
+                vault_account_info.key,
+                payer_account_info.key,
+                vault_remainder,
+            ),
+            &[
+                vault_account_info.clone(),
+                payer_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+    }
+    if market.bounty > 0 {
+        transfer_program_lamports(market_account_info, fee_destination_info, market.bounty)?;
+    }
+    close_program_account(market_account_info, payer_account_info)?;
+
+    SearchMarketEvent::MarketClosed {
+        market: *market_account_info.key,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use crate::test_utils::minimum_balance;
+    use crate::void_market_instruction;
+    use crate::LAMPORTS_PER_TOKEN;
+    use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        rent::Rent,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+
+    async fn process(
+        context: &mut ProgramTestContext,
+        instruction: Instruction,
+    ) -> Result<(), TransactionError> {
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer], recent_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    fn custom_error(error: SearchMarketError) -> TransactionError {
+        TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+    }
+
+    #[tokio::test]
+    async fn test_close_market() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let deposit = setup_deposit(
+            &deposit_keypair.pubkey(),
+            10,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let close_result = close_result_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &result,
+            &market.fee_destination,
+            &[],
+        )
+        .unwrap();
+        let close_market = close_market_instruction(&program_id, &market_key, &market).unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                deposit,
+            ],
+            Some(&context.payer.pubkey()),
+        );
+        transaction.sign(
+            &[&context.payer, &decision_authority, &deposit_keypair],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let error = process(&mut context, close_result.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(error, custom_error(SearchMarketError::MarketNotFinalized));
+
+        let void_market =
+            void_market_instruction(&program_id, &market_key, &[decision_authority.pubkey()])
+                .unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[void_market], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let error = process(&mut context, close_result.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(error, custom_error(SearchMarketError::TokensOutstanding));
+        let error = process(&mut context, close_market.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(error, custom_error(SearchMarketError::ResultsStillOpen));
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let payer_before = context
+            .banks_client
+            .get_account(decision_authority.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        let result_rent = context
+            .banks_client
+            .get_account(result_key)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        let book_rent = context
+            .banks_client
+            .get_account(book_key)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+
+        context.warp_to_slot(20).unwrap();
+        process(&mut context, close_result).await.unwrap();
+        assert!(context
+            .banks_client
+            .get_account(result_key)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(context
+            .banks_client
+            .get_account(book_key)
+            .await
+            .unwrap()
+            .is_none());
+        let fee_destination = context
+            .banks_client
+            .get_account(market.fee_destination)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee_destination.lamports, 10 * LAMPORTS_PER_TOKEN);
+
+        let market_rent = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        process(&mut context, close_market).await.unwrap();
+        assert!(context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .is_none());
+        let payer_after = context
+            .banks_client
+            .get_account(decision_authority.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        assert_eq!(
+            payer_after,
+            payer_before
+                + result_rent
+                + book_rent
+                + market_rent
+                + Rent::default().minimum_balance(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_result_refunds_resting_orders() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let deposit = setup_deposit(
+            &deposit_keypair.pubkey(),
+            10,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let mut resting_orders = vec![];
+        let mut create_orders = vec![];
+        let mut owners = vec![];
+        for (side, price) in [(OrderSide::Sell, 600), (OrderSide::Buy, 400)] {
+            let owner = Keypair::new();
+            let mut order = OrderAccount::new(
+                market_key,
+                result_key,
+                owner.pubkey(),
+                owner.pubkey(),
+                yes_token_pubkey,
+                side,
+                price,
+                5,
+                0,
+                1,
+                deposit_keypair.pubkey(),
+            );
+            let (order_key, _escrow_key, create_order) = setup_order(
+                &mut order,
+                &result.yes_mint,
+                &deposit_keypair.pubkey(),
+                &program_id,
+            );
+            program_test.add_account(
+                owner.pubkey(),
+                SolanaAccount::new(
+                    Rent::default().minimum_balance(spl_token::state::Account::LEN)
+                        + price * 5
+                        + minimum_balance(&order).unwrap(),
+                    0,
+                    &system_program::id(),
+                ),
+            );
+            resting_orders.push((order_key, order));
+            create_orders.push(create_order);
+            owners.push(owner);
+        }
+
+        let void_market =
+            void_market_instruction(&program_id, &market_key, &[decision_authority.pubkey()])
+                .unwrap();
+        let close_result = close_result_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &result,
+            &market.fee_destination,
+            &resting_orders,
+        )
+        .unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut instructions = vec![
+            create_market,
+            create_result,
+            init_yes_token,
+            init_no_token,
+            deposit,
+        ];
+        instructions.extend(create_orders);
+        instructions.push(void_market);
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&context.payer.pubkey()));
+        let mut signers = vec![&context.payer, &decision_authority, &deposit_keypair];
+        signers.extend(&owners);
+        transaction.sign(&signers, context.last_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let error = process(&mut context, close_result.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(error, custom_error(SearchMarketError::OrdersResting));
+
+        context.warp_to_slot(20).unwrap();
+        process(&mut context, close_result).await.unwrap();
+        for ((order_key, order), owner) in resting_orders.iter().zip(&owners) {
+            assert!(context
+                .banks_client
+                .get_account(*order_key)
+                .await
+                .unwrap()
+                .is_none());
+            let owner_account = context
+                .banks_client
+                .get_account(owner.pubkey())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                owner_account.lamports,
+                Rent::default().minimum_balance(spl_token::state::Account::LEN)
+                    + order.price * 5
+                    + minimum_balance(order).unwrap()
+            );
+        }
+        assert!(context
+            .banks_client
+            .get_account(result_key)
+            .await
+            .unwrap()
+            .is_none());
+
+        let yes_mint_account = context
+            .banks_client
+            .get_account(result.yes_mint)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Mint::unpack(&yes_mint_account.data).unwrap().supply, 5);
+        let fee_destination = context
+            .banks_client
+            .get_account(market.fee_destination)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee_destination.lamports, 10 * LAMPORTS_PER_TOKEN);
+    }
+}
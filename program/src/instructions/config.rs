@@ -0,0 +1,623 @@
+use super::{
+    create_pda_account, valid_tick_size, AccountType, SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::{SearchMarketError, BASIS_POINTS};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], program_id)
+}
+
+/// The upgradeable loader's data account for the program, which records its upgrade authority.
+pub fn find_program_data_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&program_id.to_bytes()], &bpf_loader_upgradeable::id())
+}
+
+/// Settings the admin can change after initialization.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigParams {
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+    pub default_tick_size: u64,
+    pub max_search_string_len: u32,
+    pub max_url_len: u32,
+    /// Slots after trading closes in which the decision authority may decide.
+    pub decision_window: u64,
+    /// Slots after a decision in which it can be challenged.
+    pub challenge_window: u64,
+    /// Lamports a challenger puts up, returned only if the arbiter overturns the decision.
+    pub challenge_bond: u64,
+    /// Rules on challenged decisions.
+    pub arbiter: Pubkey,
+    /// Share of a token's collateral paid to yes tokens of a voided market; no tokens get the rest.
+    pub void_yes_bps: u16,
+    /// Slots after settlement from which a result can be closed even though some of its tokens
+    /// were never redeemed. Their collateral then goes to the market's fee destination.
+    pub close_grace_period: u64,
+    /// Charged on the input of swaps against pools created from now on.
+    pub pool_fee_bps: u16,
+    /// Fewest tokens an order can be placed for, so dust cannot fill up a book.
+    pub min_order_quantity: u64,
+    /// Slots after a challenge in which the arbiter may rule. After them the decision stands and
+    /// the bond goes back to the challenger.
+    pub arbiter_window: u64,
+}
+
+impl ConfigParams {
+    fn validate(&self) -> ProgramResult {
+        if self.maker_fee_bps as u64 > BASIS_POINTS
+            || self.taker_fee_bps as u64 > BASIS_POINTS
+            || self.pool_fee_bps as u64 > BASIS_POINTS
+        {
+            return Err(SearchMarketError::InvalidFeeRate.into());
+        }
+        if !valid_tick_size(self.default_tick_size) {
+            return Err(SearchMarketError::InvalidTickSize.into());
+        }
+        if self.void_yes_bps as u64 > BASIS_POINTS {
+            return Err(SearchMarketError::InvalidVoidSplit.into());
+        }
+        if self.min_order_quantity == 0 {
+            return Err(SearchMarketError::OrderTooSmall.into());
+        }
+        if self.challenge_bond == 0 {
+            return Err(SearchMarketError::InvalidChallengeBond.into());
+        }
+        Ok(())
+    }
+}
+
+/// Program wide settings. While `paused`, instructions that open new positions or markets are
+/// rejected; cancelling, withdrawing, deciding and claiming keep working so no funds get stuck.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigAccount {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub admin: Pubkey,
+    /// Set by `propose_admin` and cleared once accepted; the default pubkey when none is pending.
+    pub pending_admin: Pubkey,
+    pub params: ConfigParams,
+    pub paused: bool,
+}
+
+impl ConfigAccount {
+    pub fn new(admin: Pubkey, params: ConfigParams) -> ConfigAccount {
+        ConfigAccount {
+            account_type: AccountType::Config as u8,
+            account_version: 0,
+            admin,
+            pending_admin: Pubkey::default(),
+            params,
+            paused: false,
+        }
+    }
+
+    /// Reads the config, which only `initialize_config` can create at its singleton address.
+    pub fn load(program_id: &Pubkey, account_info: &AccountInfo) -> Result<Self, ProgramError> {
+        if *account_info.owner != *program_id {
+            msg!("Config not owned by program");
+            return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+        }
+        let config = ConfigAccount::try_from_slice(&account_info.data.borrow())?;
+        if config.account_type != AccountType::Config as u8 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(config)
+    }
+
+    /// Like `load`, but fails while trading is paused.
+    pub fn load_unpaused(
+        program_id: &Pubkey,
+        account_info: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        let config = ConfigAccount::load(program_id, account_info)?;
+        if config.paused {
+            msg!("Program is paused");
+            return Err(SearchMarketError::ProgramPaused.into());
+        }
+        Ok(config)
+    }
+}
+
+fn load_as_admin(
+    program_id: &Pubkey,
+    config_account_info: &AccountInfo,
+    admin_account_info: &AccountInfo,
+) -> Result<ConfigAccount, ProgramError> {
+    let config = ConfigAccount::load(program_id, config_account_info)?;
+    if !admin_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.admin != *admin_account_info.key {
+        return Err(SearchMarketError::WrongAdmin.into());
+    }
+    Ok(config)
+}
+
+fn admin_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    instruction: SearchMarketInstruction,
+) -> Result<Instruction, std::io::Error> {
+    let (config, _config_bump_seed) = find_config_address(program_id);
+    let data = instruction.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_config_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    params: ConfigParams,
+) -> Result<Instruction, std::io::Error> {
+    let (config, config_bump_seed) = find_config_address(program_id);
+    let data = SearchMarketInstruction::InitializeConfig {
+        params,
+        config_bump_seed,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_program_data_address(program_id).0, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates the config with the signer as its admin. Only the program's upgrade authority may, so
+/// nobody can claim the admin role ahead of the deployer.
+pub fn initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    params: ConfigParams,
+    config_bump_seed: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let program_data_info = next_account_info(account_info_iter)?;
+
+    if !admin_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if *program_data_info.owner != bpf_loader_upgradeable::id()
+        || *program_data_info.key != find_program_data_address(program_id).0
+    {
+        msg!("Program data account invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    let upgrade_authority = match bincode::deserialize(&program_data_info.data.borrow()) {
+        Ok(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        }) => upgrade_authority_address,
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+    if upgrade_authority != Some(*admin_account_info.key) {
+        msg!("Config not initialized by the upgrade authority");
+        return Err(SearchMarketError::WrongAdmin.into());
+    }
+
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let config_seeds: &[&[u8]] = &[b"config", &[config_bump_seed]];
+    if Pubkey::create_program_address(config_seeds, program_id)? != *config_account_info.key {
+        msg!("Config address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    params.validate()?;
+    let config = ConfigAccount::new(*admin_account_info.key, params);
+    let data = config.try_to_vec()?;
+    create_pda_account(
+        admin_account_info,
+        config_account_info,
+        system_program_info,
+        data.len(),
+        program_id,
+        config_seeds,
+    )?;
+    config_account_info.data.borrow_mut().copy_from_slice(&data);
+
+    SearchMarketEvent::ConfigUpdated {
+        params: config.params,
+    }
+    .emit()
+}
+
+pub fn update_config_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    params: ConfigParams,
+) -> Result<Instruction, std::io::Error> {
+    admin_instruction(
+        program_id,
+        admin,
+        SearchMarketInstruction::UpdateConfig { params },
+    )
+}
+
+pub fn update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    params: ConfigParams,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let admin_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_as_admin(program_id, config_account_info, admin_account_info)?;
+    params.validate()?;
+    config.params = params;
+    config.serialize(&mut &mut config_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::ConfigUpdated {
+        params: config.params,
+    }
+    .emit()
+}
+
+pub fn set_paused_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    paused: bool,
+) -> Result<Instruction, std::io::Error> {
+    admin_instruction(
+        program_id,
+        admin,
+        SearchMarketInstruction::SetPaused { paused },
+    )
+}
+
+pub fn set_paused(program_id: &Pubkey, accounts: &[AccountInfo], paused: bool) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let admin_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_as_admin(program_id, config_account_info, admin_account_info)?;
+    msg!("Paused {}", paused);
+    config.paused = paused;
+    config.serialize(&mut &mut config_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::PausedSet { paused }.emit()
+}
+
+pub fn propose_admin_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    admin_instruction(
+        program_id,
+        admin,
+        SearchMarketInstruction::ProposeAdmin {
+            new_admin: *new_admin,
+        },
+    )
+}
+
+/// First step of an admin handover. The current admin stays in charge until `new_admin` accepts.
+pub fn propose_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let admin_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = load_as_admin(program_id, config_account_info, admin_account_info)?;
+    config.pending_admin = new_admin;
+    config.serialize(&mut &mut config_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::AdminProposed {
+        admin: config.admin,
+        pending_admin: new_admin,
+    }
+    .emit()
+}
+
+pub fn accept_admin_instruction(
+    program_id: &Pubkey,
+    new_admin: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    admin_instruction(program_id, new_admin, SearchMarketInstruction::AcceptAdmin)
+}
+
+pub fn accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let new_admin_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = ConfigAccount::load(program_id, config_account_info)?;
+    if !new_admin_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.pending_admin == Pubkey::default()
+        || config.pending_admin != *new_admin_account_info.key
+    {
+        return Err(SearchMarketError::WrongAdmin.into());
+    }
+    config.admin = *new_admin_account_info.key;
+    config.pending_admin = Pubkey::default();
+    config.serialize(&mut &mut config_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::AdminAccepted {
+        admin: config.admin,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::process_instruction;
+    use crate::test_utils::*;
+    use crate::{fill_order_instruction, OrderSide};
+    use solana_program_test::{processor, ProgramTest};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        rent::Rent,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+
+    pub fn test_config_params() -> ConfigParams {
+        ConfigParams {
+            maker_fee_bps: 0,
+            taker_fee_bps: 30,
+            default_tick_size: 1_000,
+            max_search_string_len: 200,
+            max_url_len: 200,
+            decision_window: 2,
+            challenge_window: 2,
+            challenge_bond: 1_000_000,
+            arbiter: Pubkey::default(),
+            void_yes_bps: 5_000,
+            close_grace_period: 10,
+            pool_fee_bps: 30,
+            min_order_quantity: 5,
+            arbiter_window: 4,
+        }
+    }
+
+    /// Adds an initialized, unpaused config to `program_test`.
+    pub fn setup_config(
+        program_test: &mut ProgramTest,
+        program_id: &Pubkey,
+        admin: &Pubkey,
+    ) -> ConfigAccount {
+        setup_config_with_params(program_test, program_id, admin, test_config_params())
+    }
+
+    /// Like `setup_config`, replacing any config added before.
+    pub fn setup_config_with_params(
+        program_test: &mut ProgramTest,
+        program_id: &Pubkey,
+        admin: &Pubkey,
+        params: ConfigParams,
+    ) -> ConfigAccount {
+        let config = ConfigAccount::new(*admin, params);
+        let (config_key, _config_bump_seed) = find_config_address(program_id);
+        program_test.add_account(
+            config_key,
+            SolanaAccount {
+                lamports: minimum_balance(&config).unwrap(),
+                data: config.try_to_vec().unwrap(),
+                owner: *program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        config
+    }
+
+    /// Adds the program's upgradeable loader data account with `upgrade_authority`.
+    fn setup_program_data(
+        program_test: &mut ProgramTest,
+        program_id: &Pubkey,
+        upgrade_authority: &Pubkey,
+    ) {
+        let state = UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(*upgrade_authority),
+        };
+        let data = bincode::serialize(&state).unwrap();
+        program_test.add_account(
+            find_program_data_address(program_id).0,
+            SolanaAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: bpf_loader_upgradeable::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_config_by_outsider() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let upgrade_authority = Keypair::new();
+        setup_program_data(&mut program_test, &program_id, &upgrade_authority.pubkey());
+        let outsider = Keypair::new();
+        program_test.add_account(
+            outsider.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+
+        let initialize_config =
+            initialize_config_instruction(&program_id, &outsider.pubkey(), test_config_params())
+                .unwrap();
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction =
+            Transaction::new_with_payer(&[initialize_config], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &outsider], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongAdmin as u32)
+            )
+        );
+
+        let (config_key, _config_bump_seed) = find_config_address(&program_id);
+        assert!(banks_client
+            .get_account(config_key)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_config_admin_handover_and_pause() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let admin = Keypair::new();
+        let new_admin = Keypair::new();
+        for keypair in [&admin, &new_admin] {
+            program_test.add_account(
+                keypair.pubkey(),
+                SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+            );
+        }
+        setup_program_data(&mut program_test, &program_id, &admin.pubkey());
+
+        let mut params = test_config_params();
+        let initialize_config =
+            initialize_config_instruction(&program_id, &admin.pubkey(), params.clone()).unwrap();
+        params.taker_fee_bps = 50;
+        let update_config =
+            update_config_instruction(&program_id, &admin.pubkey(), params.clone()).unwrap();
+        let propose_admin =
+            propose_admin_instruction(&program_id, &admin.pubkey(), &new_admin.pubkey()).unwrap();
+        let accept_admin = accept_admin_instruction(&program_id, &new_admin.pubkey()).unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[initialize_config, update_config, propose_admin],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (config_key, _config_bump_seed) = find_config_address(&program_id);
+        let config_account = banks_client.get_account(config_key).await.unwrap().unwrap();
+        let config = ConfigAccount::try_from_slice(&config_account.data).unwrap();
+        assert_eq!(config.admin, admin.pubkey());
+        assert_eq!(config.pending_admin, new_admin.pubkey());
+        assert_eq!(config.params, params);
+
+        let old_admin_pause = set_paused_instruction(&program_id, &admin.pubkey(), true).unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[accept_admin, old_admin_pause], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &admin, &new_admin], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(SearchMarketError::WrongAdmin as u32)
+            )
+        );
+
+        let accept_admin = accept_admin_instruction(&program_id, &new_admin.pubkey()).unwrap();
+        let pause = set_paused_instruction(&program_id, &new_admin.pubkey(), true).unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[accept_admin, pause], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let config_account = banks_client.get_account(config_key).await.unwrap().unwrap();
+        let config = ConfigAccount::try_from_slice(&config_account.data).unwrap();
+        assert_eq!(config.admin, new_admin.pubkey());
+        assert_eq!(config.pending_admin, Pubkey::default());
+        assert!(config.paused);
+
+        let fill_order = fill_order_instruction(
+            &program_id,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &new_admin.pubkey(),
+            &Pubkey::new_unique(),
+            &new_admin.pubkey(),
+            None,
+            OrderSide::Buy,
+            500,
+            1,
+            &[],
+        )
+        .unwrap();
+        let mut transaction = Transaction::new_with_payer(&[fill_order], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::ProgramPaused as u32)
+            )
+        );
+
+        params.challenge_bond = 0;
+        let update_config =
+            update_config_instruction(&program_id, &new_admin.pubkey(), params).unwrap();
+        let mut transaction = Transaction::new_with_payer(&[update_config], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidChallengeBond as u32)
+            )
+        );
+    }
+}
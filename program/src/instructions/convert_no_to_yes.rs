@@ -0,0 +1,447 @@
+use super::{
+    find_config_address, find_no_mint_address, find_vault_address, find_yes_mint_address,
+    ConfigAccount, MarketState, ResultAccount, SearchMarketAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::{SearchMarketError, BASIS_POINTS, LAMPORTS_PER_TOKEN};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction::transfer,
+    system_program,
+    sysvar::Sysvar,
+};
+
+/// `no_tokens` pairs every result of the market other than `result` with the owner's no token
+/// account for it.
+#[allow(clippy::too_many_arguments)]
+pub fn convert_no_to_yes_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    token_owner_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    yes_token_pubkey: &Pubkey,
+    no_tokens: &[(Pubkey, Pubkey)],
+    amount: u64,
+) -> Result<Instruction, std::io::Error> {
+    let (vault_key, _bump_seed) = find_vault_address(program_id, market_pubkey);
+    let data = SearchMarketInstruction::ConvertNoToYes { amount }.try_to_vec()?;
+    let mut accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(vault_key, false),
+        AccountMeta::new_readonly(*token_owner_pubkey, true),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*result_pubkey, false),
+        AccountMeta::new(find_yes_mint_address(program_id, result_pubkey).0, false),
+        AccountMeta::new(*yes_token_pubkey, false),
+    ];
+    for (other_result, no_token) in no_tokens {
+        accounts.push(AccountMeta::new(*other_result, false));
+        accounts.push(AccountMeta::new(
+            find_no_mint_address(program_id, other_result).0,
+            false,
+        ));
+        accounts.push(AccountMeta::new(*no_token, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+fn load_result(
+    program_id: &Pubkey,
+    market_key: &Pubkey,
+    result_account_info: &AccountInfo,
+) -> Result<ResultAccount, ProgramError> {
+    if *result_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.search_market != *market_key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+    Ok(result)
+}
+
+/// Burns `amount` no tokens of every result but one and mints `amount` of that result's yes tokens,
+/// paying out the collateral the no tokens held beyond them. At most one result can win, so a no
+/// token on each of the other `n - 1` results is worth a yes on the remaining one plus `n - 2`
+/// tokens' collateral. One token's collateral per set moves to the market's pool, where it backs
+/// the yes tokens the burned no tokens were paired with.
+///
+/// Refused while the rank schedule could pay more than one token across results, which would break
+/// that equivalence.
+pub fn convert_no_to_yes(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let token_owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+    let result_account_info = next_account_info(account_info_iter)?;
+    let yes_mint_account_info = next_account_info(account_info_iter)?;
+    let yes_token_account_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    ConfigAccount::load_unpaused(program_id, config_account_info)?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+    if market.state(clock.slot)? != MarketState::Open {
+        return Err(SearchMarketError::TradingClosed.into());
+    }
+    let rank_total: u64 = market.rank_schedule.iter().map(|bps| *bps as u64).sum();
+    if rank_total > BASIS_POINTS {
+        msg!("Rank schedule pays {} basis points", rank_total);
+        return Err(SearchMarketError::ResultsNotExclusive.into());
+    }
+    if market.result_count < 2 {
+        return Err(SearchMarketError::IncompleteNoSet.into());
+    }
+
+    if !token_owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let vault_seeds: &[&[u8]] = &[
+        b"vault",
+        &market_account_info.key.to_bytes(),
+        &[market.vault_bump_seed],
+    ];
+    if Pubkey::create_program_address(vault_seeds, program_id)? != *vault_account_info.key {
+        return Err(SearchMarketError::InvalidVault.into());
+    }
+
+    let collateral = amount
+        .checked_mul(LAMPORTS_PER_TOKEN)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+
+    let mut result = load_result(program_id, market_account_info.key, result_account_info)?;
+    if result.yes_mint != *yes_mint_account_info.key {
+        return Err(SearchMarketError::MintMismatch.into());
+    }
+
+    let mut converted = vec![*result_account_info.key];
+    for _ in 1..market.result_count {
+        let other_result_info = next_account_info(account_info_iter)?;
+        let no_mint_account_info = next_account_info(account_info_iter)?;
+        let no_token_account_info = next_account_info(account_info_iter)?;
+
+        let mut other_result = load_result(program_id, market_account_info.key, other_result_info)?;
+        if converted.contains(other_result_info.key) {
+            return Err(SearchMarketError::IncompleteNoSet.into());
+        }
+        converted.push(*other_result_info.key);
+        if other_result.no_mint != *no_mint_account_info.key {
+            return Err(SearchMarketError::MintMismatch.into());
+        }
+
+        msg!("burn no tokens of {}", other_result_info.key);
+        invoke(
+            &spl_token::instruction::burn(
+                spl_token_program_info.key,
+                no_token_account_info.key,
+                no_mint_account_info.key,
+                token_owner_info.key,
+                &[],
+                amount,
+            )?,
+            &[
+                no_token_account_info.clone(),
+                no_mint_account_info.clone(),
+                token_owner_info.clone(),
+                spl_token_program_info.clone(),
+            ],
+        )?;
+
+        other_result.collateral = other_result
+            .collateral
+            .checked_sub(collateral)
+            .ok_or(SearchMarketError::InsufficientCollateral)?;
+        other_result.unpaired_yes = other_result
+            .unpaired_yes
+            .checked_add(amount)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        other_result.serialize(&mut &mut other_result_info.data.borrow_mut()[..])?;
+    }
+
+    msg!("mint yes tokens of {}", result_account_info.key);
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            spl_token_program_info.key,
+            yes_mint_account_info.key,
+            yes_token_account_info.key,
+            vault_account_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            yes_mint_account_info.clone(),
+            yes_token_account_info.clone(),
+            vault_account_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+        &[vault_seeds],
+    )?;
+    result.unpaired_yes = result
+        .unpaired_yes
+        .checked_add(amount)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    result.serialize(&mut &mut result_account_info.data.borrow_mut()[..])?;
+
+    let payout = collateral
+        .checked_mul(market.result_count - 2)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if payout > 0 {
+        msg!("transfer sol vault to destination");
+        invoke_signed(
+            &transfer(vault_account_info.key, destination_info.key, payout),
+            &[
+                vault_account_info.clone(),
+                destination_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+    }
+
+    market.pooled_collateral = market
+        .pooled_collateral
+        .checked_add(collateral)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::ConvertedNoToYes {
+        market: *market_account_info.key,
+        result: *result_account_info.key,
+        owner: *token_owner_info.key,
+        amount,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use solana_program::program_pack::Pack;
+    use solana_program_test::{processor, ProgramTest};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+    use spl_token::state::Account;
+
+    #[tokio::test]
+    async fn test_convert_no_to_yes() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 10, &mut program_test, &program_id);
+
+        let owner = Keypair::new();
+        let destination = Pubkey::new_unique();
+        program_test.add_account(
+            destination,
+            SolanaAccount::new(1_000_000, 0, &system_program::id()),
+        );
+
+        let mut instructions = vec![create_market];
+        let mut depositors = vec![];
+        let mut results = vec![];
+        for index in 0..3 {
+            let mut result = ResultAccount::new(
+                market_key,
+                format!("http://cyberpunk.net/{}", index),
+                String::from("Cyberpunk website"),
+                String::from("A game fated to be legend"),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            );
+            let (result_key, create_result) = setup_result(
+                &mut result,
+                index,
+                &decision_authority.pubkey(),
+                &program_id,
+            );
+            let (yes_token, init_yes_token) =
+                setup_token(&result.yes_mint, &owner.pubkey(), &mut program_test);
+            let (no_token, init_no_token) =
+                setup_token(&result.no_mint, &owner.pubkey(), &mut program_test);
+            let depositor = Keypair::new();
+            let deposit = setup_deposit(
+                &depositor.pubkey(),
+                10,
+                &market_key,
+                &result_key,
+                &result,
+                &yes_token,
+                &no_token,
+                &mut program_test,
+                &program_id,
+            );
+            instructions.extend([create_result, init_yes_token, init_no_token, deposit]);
+            depositors.push(depositor);
+            results.push((result_key, yes_token, no_token));
+        }
+
+        let (result_key, yes_token, _no_token) = results[0];
+        let no_tokens: Vec<(Pubkey, Pubkey)> = results[1..]
+            .iter()
+            .map(|(other_result, _yes_token, no_token)| (*other_result, *no_token))
+            .collect();
+        let convert = convert_no_to_yes_instruction(
+            &program_id,
+            &market_key,
+            &owner.pubkey(),
+            &destination,
+            &result_key,
+            &yes_token,
+            &no_tokens,
+            2,
+        )
+        .unwrap();
+        let repeated = convert_no_to_yes_instruction(
+            &program_id,
+            &market_key,
+            &owner.pubkey(),
+            &destination,
+            &result_key,
+            &yes_token,
+            &[no_tokens[0], no_tokens[0]],
+            1,
+        )
+        .unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut signers = vec![&context.payer, &decision_authority];
+        signers.extend(depositors.iter());
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&context.payer.pubkey()));
+        transaction.sign(&signers, context.last_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[repeated], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, &owner], context.last_blockhash);
+        assert_eq!(
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::IncompleteNoSet as u32)
+            )
+        );
+
+        let mut transaction =
+            Transaction::new_with_payer(&[convert], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, &owner], context.last_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let destination_account = context
+            .banks_client
+            .get_account(destination)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            destination_account.lamports,
+            1_000_000 + 2 * LAMPORTS_PER_TOKEN
+        );
+
+        let market_account = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let market = SearchMarketAccount::try_from_slice(&market_account.data).unwrap();
+        assert_eq!(market.pooled_collateral, 2 * LAMPORTS_PER_TOKEN);
+
+        for (index, (result_key, yes_token, no_token)) in results.iter().enumerate() {
+            let result_account = context
+                .banks_client
+                .get_account(*result_key)
+                .await
+                .unwrap()
+                .unwrap();
+            let result = ResultAccount::try_from_slice(&result_account.data).unwrap();
+            assert_eq!(result.unpaired_yes, 2);
+
+            let yes_account = context
+                .banks_client
+                .get_account(*yes_token)
+                .await
+                .unwrap()
+                .unwrap();
+            let no_account = context
+                .banks_client
+                .get_account(*no_token)
+                .await
+                .unwrap()
+                .unwrap();
+            let yes_amount = Account::unpack(&yes_account.data).unwrap().amount;
+            let no_amount = Account::unpack(&no_account.data).unwrap().amount;
+            if index == 0 {
+                assert_eq!(result.collateral, 10 * LAMPORTS_PER_TOKEN);
+                assert_eq!((yes_amount, no_amount), (12, 10));
+            } else {
+                assert_eq!(result.collateral, 8 * LAMPORTS_PER_TOKEN);
+                assert_eq!((yes_amount, no_amount), (10, 8));
+            }
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use super::{AccountType, SearchMarketInstruction};
+use super::{
+    create_pda_account, find_config_address, lmsr, AccountType, ConfigAccount, ResultAccount,
+    SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::{SearchMarketError, BASIS_POINTS, LAMPORTS_PER_TOKEN};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
@@ -6,10 +10,14 @@ use solana_program::{
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
     msg,
+    program::invoke,
     program_error::ProgramError,
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
     sysvar::Sysvar,
 };
+use std::convert::TryFrom;
 
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub struct SearchMarketAccount {
@@ -19,6 +27,142 @@ pub struct SearchMarketAccount {
     pub search_string: String,
     pub best_result: Pubkey,
     pub expires_slot: Slot,
+    pub tick_size: u64,
+    pub vault_bump_seed: u8,
+    pub result_count: u64,
+    /// Lamports held on the market account on top of its rent, paid out on the decision.
+    pub bounty: u64,
+    pub bounty_refund_bps: u16,
+    pub fee_destination: Pubkey,
+    /// Stored `MarketState`; read it through `state`, which also accounts for trading closing.
+    pub state: u8,
+    /// Last slot `decide` is accepted in. After it an undecided market finalizes with no winner.
+    pub decision_deadline: Slot,
+    pub challenge_window: u64,
+    pub challenge_bond: u64,
+    pub decided_slot: Slot,
+    /// Set once the decision is challenged; the default pubkey while unchallenged.
+    pub challenger: Pubkey,
+    /// When not empty, `decision_threshold` of these keys must sign in place of
+    /// `decision_authority`. Fixed at creation so the account never changes size.
+    pub decision_signers: Vec<Pubkey>,
+    pub decision_threshold: u8,
+    /// May decide alone until `delegate_expires_slot`; the default pubkey when none is set.
+    pub delegate: Pubkey,
+    pub delegate_expires_slot: Slot,
+    /// Decided with `decide_weighted`, so each result's `payout_bps` sets its payout.
+    pub weighted: bool,
+    /// Basis points paid to the yes tokens of the result ranked at each position by
+    /// `decide_ranked`.
+    pub rank_schedule: Vec<u16>,
+    /// Ranked results, best first, padded with the default pubkey. Allocated at the schedule's
+    /// length on creation so the account never changes size.
+    pub ranking: Vec<Pubkey>,
+    /// Taken from the config when the market is voided.
+    pub void_yes_bps: u16,
+    /// Slot the market was finalized or voided in, from which the close grace period runs.
+    pub settled_slot: Slot,
+    /// Funded the market account and gets its rent back when it is closed.
+    pub payer: Pubkey,
+    pub closed_results: u64,
+    /// Backs the yes tokens `convert_no_to_yes` leaves without a no token, one token's collateral
+    /// per converted set. Redemptions draw on it once a result's own collateral runs out.
+    pub pooled_collateral: u64,
+    /// Liquidity in tokens each result's market maker is created with; zero for no market makers.
+    pub amm_liquidity: u64,
+    /// Lamports in the vault set aside for the market makers of results not yet created.
+    pub amm_subsidy: u64,
+    /// Slots after `challenged_slot` in which the arbiter may rule on a challenge.
+    pub arbiter_window: u64,
+    pub challenged_slot: Slot,
+}
+
+pub const MAX_DECISION_SIGNERS: usize = 10;
+pub const MAX_RANKED_RESULTS: usize = 10;
+
+/// Settings the creator chooses for a new market.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct MarketParams {
+    /// Slots from creation until trading closes.
+    pub expires_slot_offset: u64,
+    /// The config's default tick size when `None`.
+    pub tick_size: Option<u64>,
+    /// Lamports the creator puts up for whoever decides the market.
+    pub bounty: u64,
+    pub bounty_refund_bps: u16,
+    pub decision_signers: Vec<Pubkey>,
+    pub decision_threshold: u8,
+    pub rank_schedule: Vec<u16>,
+    pub amm_liquidity: u64,
+    pub amm_subsidy: u64,
+}
+
+impl MarketParams {
+    fn validate(&self) -> ProgramResult {
+        if self.expires_slot_offset == 0 {
+            return Err(SearchMarketError::InvalidExpiry.into());
+        }
+        if self.bounty_refund_bps as u64 > BASIS_POINTS {
+            return Err(SearchMarketError::InvalidBountyShare.into());
+        }
+        if !valid_decision_signers(&self.decision_signers, self.decision_threshold) {
+            msg!(
+                "Threshold {} of {} decision signers",
+                self.decision_threshold,
+                self.decision_signers.len()
+            );
+            return Err(SearchMarketError::InvalidDecisionSigners.into());
+        }
+        if self.rank_schedule.len() > MAX_RANKED_RESULTS
+            || self
+                .rank_schedule
+                .iter()
+                .any(|bps| *bps as u64 > BASIS_POINTS)
+        {
+            return Err(SearchMarketError::InvalidRankSchedule.into());
+        }
+        let result_subsidy = match self.amm_liquidity {
+            0 => 0,
+            liquidity => lmsr::subsidy(liquidity).ok_or(SearchMarketError::ArithmeticOverflow)?,
+        };
+        if self.amm_subsidy < result_subsidy || (self.amm_liquidity == 0 && self.amm_subsidy > 0) {
+            msg!(
+                "Subsidy {} does not fund market makers needing {}",
+                self.amm_subsidy,
+                result_subsidy
+            );
+            return Err(SearchMarketError::InvalidAmmSubsidy.into());
+        }
+        Ok(())
+    }
+}
+
+/// A market trades while `Open` and stops at `expires_slot`. The decision authority then records
+/// the best result, which can be challenged for `challenge_window` slots before it is `Finalized`
+/// and winning tokens can be redeemed. Until then it can instead be `Voided`, after which every
+/// token redeems at the void split.
+#[derive(BorshSerialize, BorshDeserialize, Copy, Clone, Debug, PartialEq)]
+pub enum MarketState {
+    Open,
+    TradingClosed,
+    Decided,
+    Finalized,
+    Voided,
+}
+
+impl TryFrom<u8> for MarketState {
+    type Error = SearchMarketError;
+
+    fn try_from(val: u8) -> Result<Self, Self::Error> {
+        match val {
+            0 => Ok(MarketState::Open),
+            1 => Ok(MarketState::TradingClosed),
+            2 => Ok(MarketState::Decided),
+            3 => Ok(MarketState::Finalized),
+            4 => Ok(MarketState::Voided),
+            _ => Err(SearchMarketError::InvalidMarketState),
+        }
+    }
 }
 
 impl SearchMarketAccount {
@@ -26,6 +170,8 @@ impl SearchMarketAccount {
         decision_authority: Pubkey,
         search_string: String,
         expires_slot: Slot,
+        tick_size: u64,
+        vault_bump_seed: u8,
     ) -> SearchMarketAccount {
         SearchMarketAccount {
             account_type: AccountType::SearchMarket as u8,
@@ -33,64 +179,322 @@ impl SearchMarketAccount {
             decision_authority,
             search_string,
             expires_slot,
+            tick_size,
             best_result: Pubkey::default(),
+            vault_bump_seed,
+            result_count: 0,
+            bounty: 0,
+            bounty_refund_bps: 0,
+            fee_destination: Pubkey::default(),
+            state: MarketState::Open as u8,
+            decision_deadline: expires_slot,
+            challenge_window: 0,
+            challenge_bond: 0,
+            decided_slot: 0,
+            challenger: Pubkey::default(),
+            decision_signers: vec![],
+            decision_threshold: 0,
+            delegate: Pubkey::default(),
+            delegate_expires_slot: 0,
+            weighted: false,
+            rank_schedule: vec![],
+            ranking: vec![],
+            void_yes_bps: 0,
+            settled_slot: 0,
+            payer: Pubkey::default(),
+            closed_results: 0,
+            pooled_collateral: 0,
+            amm_liquidity: 0,
+            amm_subsidy: 0,
+            arbiter_window: 0,
+            challenged_slot: 0,
+        }
+    }
+
+    /// Last slot the arbiter may rule on a challenge in.
+    pub fn arbiter_deadline(&self) -> Slot {
+        self.challenged_slot.saturating_add(self.arbiter_window)
+    }
+
+    /// Lamports that one of `result`'s yes and one of its no tokens redeem for once the market is
+    /// settled. Together they always make up a token's collateral.
+    pub fn redemption_values(&self, result_key: &Pubkey, result: &ResultAccount) -> (u64, u64) {
+        let rank = self.ranking.iter().position(|ranked| ranked == result_key);
+        let yes_bps = if self.state == MarketState::Voided as u8 {
+            self.void_yes_bps as u64
+        } else if self.weighted {
+            result.payout_bps as u64
+        } else if let Some(rank) = rank {
+            self.rank_schedule[rank] as u64
+        } else if self.best_result == *result_key {
+            BASIS_POINTS
+        } else {
+            0
+        };
+        let yes_value = LAMPORTS_PER_TOKEN * yes_bps / BASIS_POINTS;
+        (yes_value, LAMPORTS_PER_TOKEN - yes_value)
+    }
+
+    /// Whether `signers` speak for the market's decision authority, either the single key or the
+    /// threshold of the signer set.
+    pub fn approved_by(&self, signers: &[Pubkey]) -> bool {
+        if self.decision_signers.is_empty() {
+            return signers.contains(&self.decision_authority);
         }
+        let approvals = self
+            .decision_signers
+            .iter()
+            .filter(|signer| signers.contains(signer))
+            .count();
+        approvals >= self.decision_threshold as usize
+    }
+
+    /// Like `approved_by`, but an unexpired delegate may also decide alone.
+    pub fn can_decide(&self, signers: &[Pubkey], slot: Slot) -> bool {
+        let delegate_active = self.delegate != Pubkey::default()
+            && slot <= self.delegate_expires_slot
+            && signers.contains(&self.delegate);
+        delegate_active || self.approved_by(signers)
+    }
+
+    pub fn state(&self, slot: Slot) -> Result<MarketState, SearchMarketError> {
+        Ok(match MarketState::try_from(self.state)? {
+            MarketState::Open if slot > self.expires_slot => MarketState::TradingClosed,
+            state => state,
+        })
     }
+
+    /// Whether `price` lies strictly between zero and a token's collateral on the market's ticks.
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    pub fn valid_price(&self, price: u64) -> bool {
+        price != 0 && price < LAMPORTS_PER_TOKEN && price % self.tick_size == 0
+    }
+
+    /// Whether tokens can be redeemed, which no longer depends on the slot.
+    pub fn is_settled(&self) -> bool {
+        self.state == MarketState::Finalized as u8 || self.state == MarketState::Voided as u8
+    }
+}
+
+pub fn find_market_address(program_id: &Pubkey, creator: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"market", &creator.to_bytes(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// A tick must split a token's collateral into a whole number of steps, at least two of them.
+#[allow(unknown_lints, clippy::manual_is_multiple_of)]
+pub(crate) fn valid_tick_size(tick_size: u64) -> bool {
+    tick_size != 0 && tick_size < LAMPORTS_PER_TOKEN && LAMPORTS_PER_TOKEN % tick_size == 0
+}
+
+/// An empty set keeps the single decision authority. Otherwise the keys must be distinct and the
+/// threshold reachable.
+fn valid_decision_signers(signers: &[Pubkey], threshold: u8) -> bool {
+    if signers.is_empty() {
+        return threshold == 0;
+    }
+    let distinct = signers
+        .iter()
+        .enumerate()
+        .all(|(i, signer)| !signers[..i].contains(signer));
+    distinct
+        && signers.len() <= MAX_DECISION_SIGNERS
+        && threshold > 0
+        && threshold as usize <= signers.len()
+}
+
+pub fn find_vault_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault", &market.to_bytes()], program_id)
 }
 
 pub fn create_market(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
-    expires_slot_offset: u64,
     search_string: String,
+    nonce: u64,
+    market_bump_seed: u8,
+    vault_bump_seed: u8,
+    params: MarketParams,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let market_account_info = next_account_info(account_info_iter)?;
     let decision_authority_info = next_account_info(account_info_iter)?;
-    let clock = Clock::get().unwrap();
+    let creator_account_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    let config = ConfigAccount::load_unpaused(program_id, config_account_info)?;
+    if search_string.len() > config.params.max_search_string_len as usize {
+        msg!(
+            "Search string longer than {}",
+            config.params.max_search_string_len
+        );
+        return Err(SearchMarketError::StringTooLong.into());
+    }
+
+    let tick_size = params.tick_size.unwrap_or(config.params.default_tick_size);
+    if !valid_tick_size(tick_size) {
+        msg!(
+            "Tick size {} does not divide {}",
+            tick_size,
+            LAMPORTS_PER_TOKEN
+        );
+        return Err(SearchMarketError::InvalidTickSize.into());
+    }
+    params.validate()?;
 
-    msg!("Expires Slot Offset {}", expires_slot_offset);
-    if expires_slot_offset == 0 {
-        return Err(ProgramError::InvalidArgument);
+    if !decision_authority_info.is_signer || !creator_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
-    if !market_account_info.data.borrow().iter().all(|&b| b == 0) {
-        return Err(ProgramError::AccountAlreadyInitialized);
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
     }
 
-    if !decision_authority_info.is_signer {
-        return Err(ProgramError::NotEnoughAccountKeys);
+    let market_seeds: &[&[u8]] = &[
+        b"market",
+        &creator_account_info.key.to_bytes(),
+        &nonce.to_le_bytes(),
+        &[market_bump_seed],
+    ];
+    if Pubkey::create_program_address(market_seeds, program_id)? != *market_account_info.key {
+        msg!("Market address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
     }
 
-    let search_market = SearchMarketAccount::new(
+    match Pubkey::create_program_address(
+        &[
+            b"vault",
+            &market_account_info.key.to_bytes(),
+            &[vault_bump_seed],
+        ],
+        program_id,
+    ) {
+        Ok(vault_key) if vault_key == *vault_account_info.key => {}
+        _ => {
+            msg!("Vault bump seed invalid");
+            return Err(SearchMarketError::InvalidVault.into());
+        }
+    }
+
+    let expires_slot = clock
+        .slot
+        .checked_add(params.expires_slot_offset)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    let mut search_market = SearchMarketAccount::new(
         *decision_authority_info.key,
         search_string,
-        clock.slot + expires_slot_offset,
+        expires_slot,
+        tick_size,
+        vault_bump_seed,
     );
+    search_market.bounty = params.bounty;
+    search_market.bounty_refund_bps = params.bounty_refund_bps;
+    search_market.fee_destination = *fee_destination_info.key;
+    search_market.decision_deadline = expires_slot
+        .checked_add(config.params.decision_window)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    search_market.challenge_window = config.params.challenge_window;
+    search_market.challenge_bond = config.params.challenge_bond;
+    search_market.arbiter_window = config.params.arbiter_window;
+    search_market.decision_signers = params.decision_signers;
+    search_market.decision_threshold = params.decision_threshold;
+    search_market.ranking = vec![Pubkey::default(); params.rank_schedule.len()];
+    search_market.rank_schedule = params.rank_schedule;
+    search_market.payer = *creator_account_info.key;
+    search_market.amm_liquidity = params.amm_liquidity;
+    search_market.amm_subsidy = params.amm_subsidy;
+
+    let data = search_market.try_to_vec()?;
+    create_pda_account(
+        creator_account_info,
+        market_account_info,
+        system_program_info,
+        data.len(),
+        program_id,
+        market_seeds,
+    )?;
 
-    let result = search_market
-        .serialize(&mut &mut market_account_info.data.borrow_mut()[..])
-        .map(|_| ())
-        .map_err(|e| e.into());
+    market_account_info.data.borrow_mut().copy_from_slice(&data);
 
-    return result;
+    if params.bounty > 0 {
+        invoke(
+            &system_instruction::transfer(
+                creator_account_info.key,
+                market_account_info.key,
+                params.bounty,
+            ),
+            &[
+                creator_account_info.clone(),
+                market_account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    // The vault is kept rent exempt on top of the collateral it holds, so paying out every token
+    // never leaves it below the minimum.
+    let vault_funding = Rent::get()?
+        .minimum_balance(0)
+        .saturating_sub(vault_account_info.lamports())
+        .checked_add(params.amm_subsidy)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if vault_funding > 0 {
+        invoke(
+            &system_instruction::transfer(
+                creator_account_info.key,
+                vault_account_info.key,
+                vault_funding,
+            ),
+            &[
+                creator_account_info.clone(),
+                vault_account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    SearchMarketEvent::MarketCreated {
+        market: *market_account_info.key,
+        creator: *creator_account_info.key,
+        decision_authority: search_market.decision_authority,
+        search_string: search_market.search_string,
+        expires_slot,
+        tick_size,
+    }
+    .emit()
 }
 
 pub fn create_market_instruction(
     program_id: &Pubkey,
-    market_pubkey: &Pubkey,
+    creator_pubkey: &Pubkey,
     decision_pubkey: &Pubkey,
-    expires_slot_offset: u64,
+    fee_destination_pubkey: &Pubkey,
+    nonce: u64,
     search_string: String,
+    params: MarketParams,
 ) -> Result<Instruction, std::io::Error> {
+    let (market_pubkey, market_bump_seed) = find_market_address(program_id, creator_pubkey, nonce);
+    let (vault_key, vault_bump_seed) = find_vault_address(program_id, &market_pubkey);
     let data = SearchMarketInstruction::CreateMarket {
-        expires_slot_offset,
         search_string,
+        nonce,
+        market_bump_seed,
+        vault_bump_seed,
+        params,
     }
     .try_to_vec()?;
     let accounts = vec![
-        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(market_pubkey, false),
         AccountMeta::new_readonly(*decision_pubkey, true),
+        AccountMeta::new(*creator_pubkey, true),
+        AccountMeta::new_readonly(*fee_destination_pubkey, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(vault_key, false),
     ];
     Ok(Instruction {
         program_id: *program_id,
@@ -103,35 +507,58 @@ pub fn create_market_instruction(
 #[cfg(feature = "test-bpf")]
 pub mod test {
     use super::*;
+    use crate::instructions::test_utils::setup_config;
     use crate::process_instruction;
     use crate::test_utils::*;
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::{
         account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        rent::Rent,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
 
     pub fn setup_market(
-        market: &SearchMarketAccount,
+        market: &mut SearchMarketAccount,
         expires_slot_offset: u64,
         program_test: &mut ProgramTest,
         program_id: &Pubkey,
     ) -> (Pubkey, Instruction) {
-        let market_key = Pubkey::new_unique();
-        let market_account = SolanaAccount::new(
-            minimum_balance(&market).unwrap(),
-            space(&market).unwrap(),
-            program_id,
-        );
-        program_test.add_account(market_key, market_account);
+        let config = setup_config(program_test, program_id, &Pubkey::new_unique());
+        market.decision_deadline = market.expires_slot + config.params.decision_window;
+        market.challenge_window = config.params.challenge_window;
+        market.challenge_bond = config.params.challenge_bond;
+        market.arbiter_window = config.params.arbiter_window;
+        market.ranking = vec![Pubkey::default(); market.rank_schedule.len()];
+        market.payer = market.decision_authority;
+        let creator_account = SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id());
+        program_test.add_account(market.decision_authority, creator_account);
+
+        let (market_key, _market_bump_seed) =
+            find_market_address(program_id, &market.decision_authority, 0);
+        let (_vault_key, vault_bump_seed) = find_vault_address(program_id, &market_key);
+        market.vault_bump_seed = vault_bump_seed;
 
         let instruction = create_market_instruction(
             program_id,
-            &market_key,
             &market.decision_authority,
-            expires_slot_offset,
+            &market.decision_authority,
+            &market.fee_destination,
+            0,
             market.search_string.clone(),
+            MarketParams {
+                expires_slot_offset,
+                tick_size: Some(market.tick_size),
+                bounty: market.bounty,
+                bounty_refund_bps: market.bounty_refund_bps,
+                decision_signers: market.decision_signers.clone(),
+                decision_threshold: market.decision_threshold,
+                rank_schedule: market.rank_schedule.clone(),
+                amm_liquidity: market.amm_liquidity,
+                amm_subsidy: market.amm_subsidy,
+            },
         )
         .unwrap();
         return (market_key, instruction);
@@ -144,9 +571,18 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.bounty = 1_000_000;
+        market.bounty_refund_bps = 2_500;
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let mut transaction = Transaction::new_with_payer(&[create_market], Some(&payer.pubkey()));
@@ -154,8 +590,53 @@ pub mod test {
         banks_client.process_transaction(transaction).await.unwrap();
 
         let market_account = banks_client.get_account(market_key).await.unwrap().unwrap();
+        assert_eq!(market_account.owner, program_id);
+        assert_eq!(
+            market_account.lamports,
+            minimum_balance(&market).unwrap() + 1_000_000
+        );
         let processed_market =
             SearchMarketAccount::try_from_slice(&market_account.data[..]).unwrap();
         assert_eq!(market, processed_market);
+
+        let (vault_key, _vault_bump_seed) = find_vault_address(&program_id, &market_key);
+        let vault_account = banks_client.get_account(vault_key).await.unwrap().unwrap();
+        assert_eq!(vault_account.lamports, Rent::default().minimum_balance(0));
+    }
+
+    #[tokio::test]
+    async fn test_create_market_wrong_address() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (_market_key, mut create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+        let (other_market_key, _bump_seed) =
+            find_market_address(&program_id, &decision_authority.pubkey(), 1);
+        create_market.accounts[0].pubkey = other_market_key;
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction = Transaction::new_with_payer(&[create_market], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &decision_authority], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidAccountAddress as u32)
+            )
+        );
     }
 }
@@ -1,5 +1,11 @@
-use super::{SearchMarketAccount, SearchMarketInstruction};
+use super::{
+    close_order, create_pda_account, fee_amount, find_book_address, find_config_address,
+    find_fee_vault_address, find_no_mint_address, find_vault_address, find_yes_mint_address,
+    ConfigAccount, MarketState, OrderBookAccount, PairMint, ResultAccount, SearchMarketAccount,
+    SearchMarketEvent, SearchMarketInstruction,
+};
 use crate::instructions::AccountType;
+use crate::{SearchMarketError, LAMPORTS_PER_TOKEN};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
@@ -15,10 +21,20 @@ use solana_program::{
     system_instruction, system_program,
     sysvar::{rent, Sysvar},
 };
+use spl_token::state::Account;
+
+pub fn find_order_address(program_id: &Pubkey, owner: &Pubkey, client_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"order", &owner.to_bytes(), &client_id.to_le_bytes()],
+        program_id,
+    )
+}
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_order_instruction(
     program_id: &Pubkey,
-    order: &Pubkey,
+    client_id: u64,
+    owner: &Pubkey,
     search_market: &Pubkey,
     result: &Pubkey,
     sol_account: &Pubkey,
@@ -26,38 +42,51 @@ pub fn create_order_instruction(
     token_mint_account: &Pubkey,
     token_authority_account: &Pubkey,
     execution_authority: &Pubkey,
+    referrer: Option<Pubkey>,
     side: OrderSide,
     price: u64,
     quantity: u64,
+    makers: &[(Pubkey, OrderAccount)],
 ) -> Result<Instruction, std::io::Error> {
-    let escrow_name: &[u8] = match side {
-        OrderSide::Buy => b"sol_escrow",
-        OrderSide::Sell => b"token_escrow",
-    };
+    let (order, order_bump_seed) = find_order_address(program_id, owner, client_id);
+    let (book, _book_bump_seed) = find_book_address(program_id, result);
+    let (fee_vault, fee_vault_bump_seed) =
+        find_fee_vault_address(program_id, &referrer.unwrap_or_default());
     let (escrow_key, bump_seed) =
-        Pubkey::find_program_address(&[escrow_name, &order.to_bytes()], program_id);
-    let is_buy_side = side == OrderSide::Buy;
+        Pubkey::find_program_address(&[escrow_name(side), &order.to_bytes()], program_id);
     let data = SearchMarketInstruction::CreateOrder {
         side: side as u8,
         price,
         quantity,
+        client_id,
+        order_bump_seed,
         escrow_bump_seed: bump_seed,
+        referrer,
+        fee_vault_bump_seed,
     }
     .try_to_vec()?;
-    let accounts = vec![
-        AccountMeta::new(*order, false),
+    let mut accounts = vec![
+        AccountMeta::new(order, false),
+        AccountMeta::new_readonly(*owner, true),
         AccountMeta::new_readonly(*search_market, false),
-        AccountMeta::new_readonly(*result, false),
+        AccountMeta::new(*result, false),
+        AccountMeta::new(book, false),
         AccountMeta::new(*sol_account, true),
         AccountMeta::new(*token_account, false),
         AccountMeta::new_readonly(*token_mint_account, false),
-        AccountMeta::new_readonly(*token_authority_account, !is_buy_side),
+        AccountMeta::new_readonly(*token_authority_account, side == OrderSide::Sell),
         AccountMeta::new(escrow_key, false),
         AccountMeta::new_readonly(*execution_authority, true),
+        AccountMeta::new(fee_vault, false),
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(rent::id(), false),
         AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
     ];
+    accounts.extend(pair_mint_account_metas(program_id, search_market, result));
+    for (maker_key, maker) in makers {
+        accounts.extend(maker_account_metas(program_id, maker_key, maker));
+    }
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -65,10 +94,101 @@ pub fn create_order_instruction(
     })
 }
 
+/// Accounts a taker passes for each resting order it expects to trade against, in book order.
+pub fn maker_account_metas(
+    program_id: &Pubkey,
+    maker_key: &Pubkey,
+    maker: &OrderAccount,
+) -> Vec<AccountMeta> {
+    let (escrow_key, _bump_seed) = Pubkey::find_program_address(
+        &[escrow_name(maker.side.into()), &maker_key.to_bytes()],
+        program_id,
+    );
+    let (fee_vault, _fee_vault_bump_seed) =
+        find_fee_vault_address(program_id, &maker.referrer.unwrap_or_default());
+    vec![
+        AccountMeta::new(*maker_key, false),
+        AccountMeta::new(escrow_key, false),
+        AccountMeta::new(maker.sol_account, false),
+        AccountMeta::new(maker.token_account, false),
+        AccountMeta::new(fee_vault, false),
+    ]
+}
+
+/// The vault and mints that complementary buys mint a pair from, passed before any makers.
+pub(crate) fn pair_mint_account_metas(
+    program_id: &Pubkey,
+    search_market: &Pubkey,
+    result: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(find_vault_address(program_id, search_market).0, false),
+        AccountMeta::new(find_yes_mint_address(program_id, result).0, false),
+        AccountMeta::new(find_no_mint_address(program_id, result).0, false),
+    ]
+}
+
+/// Derives the fee vault that fees for `referrer` accrue to from its recorded bump seed.
+pub(crate) fn fee_vault_address(
+    program_id: &Pubkey,
+    referrer: &Option<Pubkey>,
+    fee_vault_bump_seed: u8,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[
+            b"fees",
+            &referrer.unwrap_or_default().to_bytes(),
+            &[fee_vault_bump_seed],
+        ],
+        program_id,
+    )
+    .map_err(|e| e.into())
+}
+
+pub(crate) fn escrow_name(side: OrderSide) -> &'static [u8] {
+    match side {
+        OrderSide::Buy | OrderSide::BuyNo => b"sol_escrow",
+        OrderSide::Sell => b"token_escrow",
+    }
+}
+
+/// `Buy` and `Sell` trade existing yes tokens. `BuyNo` bids for no tokens; it fills against a `Buy`
+/// by minting a fresh pair, each buyer paying its own price and the two prices summing to a
+/// token's collateral.
 #[derive(BorshSerialize, BorshDeserialize, Copy, Clone, Debug, PartialEq)]
 pub enum OrderSide {
     Buy,
     Sell,
+    BuyNo,
+}
+
+impl OrderSide {
+    /// The side of the book holding the orders this one trades with.
+    pub fn opposite(self) -> OrderSide {
+        match self {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell | OrderSide::BuyNo => OrderSide::Buy,
+        }
+    }
+
+    /// The yes price an order at `price` rests at in the book.
+    pub fn book_price(self, price: u64) -> Result<u64, SearchMarketError> {
+        match self {
+            OrderSide::Buy | OrderSide::Sell => Ok(price),
+            OrderSide::BuyNo => LAMPORTS_PER_TOKEN
+                .checked_sub(price)
+                .ok_or(SearchMarketError::InvalidPrice),
+        }
+    }
+
+    /// Whether an incoming order on this side at book price `price` trades with a resting order at
+    /// `resting_price`.
+    pub fn crosses(self, price: u64, resting_price: u64) -> bool {
+        match self {
+            OrderSide::Buy => price >= resting_price,
+            OrderSide::Sell | OrderSide::BuyNo => price <= resting_price,
+        }
+    }
 }
 
 impl From<u8> for OrderSide {
@@ -76,6 +196,7 @@ impl From<u8> for OrderSide {
         match val {
             0 => OrderSide::Buy,
             1 => OrderSide::Sell,
+            2 => OrderSide::BuyNo,
             _ => panic!("Unknown order side"),
         }
     }
@@ -87,6 +208,7 @@ pub struct OrderAccount {
     pub account_version: u8,
     pub search_market: Pubkey,
     pub result: Pubkey,
+    pub owner: Pubkey,
     pub sol_account: Pubkey,
     pub token_account: Pubkey,
     pub side: u8,
@@ -95,12 +217,18 @@ pub struct OrderAccount {
     pub escrow_bump_seed: u8,
     pub creation_slot: Slot,
     pub execution_authority: Pubkey,
+    /// Frontend whose fee vault collects the fees this order pays as a maker.
+    pub referrer: Option<Pubkey>,
+    pub fee_vault_bump_seed: u8,
+    pub maker_fee_bps: u16,
 }
 
 impl OrderAccount {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         search_market: Pubkey,
         result: Pubkey,
+        owner: Pubkey,
         sol_account: Pubkey,
         token_account: Pubkey,
         side: OrderSide,
@@ -115,6 +243,7 @@ impl OrderAccount {
             account_version: 0,
             search_market,
             result,
+            owner,
             sol_account,
             token_account,
             side: side as u8,
@@ -123,48 +252,188 @@ impl OrderAccount {
             escrow_bump_seed,
             creation_slot,
             execution_authority,
+            referrer: None,
+            fee_vault_bump_seed: 0,
+            maker_fee_bps: 0,
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_order(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     side: u8,
     price: u64,
     quantity: u64,
+    client_id: u64,
+    order_bump_seed: u8,
     escrow_bump_seed: u8,
+    referrer: Option<Pubkey>,
+    fee_vault_bump_seed: u8,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let order_account_info = next_account_info(account_info_iter)?;
+    let owner_account_info = next_account_info(account_info_iter)?;
     let market_account_info = next_account_info(account_info_iter)?;
     let result_account_info = next_account_info(account_info_iter)?;
+    let book_account_info = next_account_info(account_info_iter)?;
     let sol_account_info = next_account_info(account_info_iter)?;
     let token_account_info = next_account_info(account_info_iter)?;
     let token_mint_account_info = next_account_info(account_info_iter)?;
     let token_authority_account_info = next_account_info(account_info_iter)?;
     let escrow_account_info = next_account_info(account_info_iter)?;
     let execution_authority_account_info = next_account_info(account_info_iter)?;
+    let fee_vault_account_info = next_account_info(account_info_iter)?;
     let spl_token_program_info = next_account_info(account_info_iter)?;
     let rent_account_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let yes_mint_account_info = next_account_info(account_info_iter)?;
+    let no_mint_account_info = next_account_info(account_info_iter)?;
 
+    let config = ConfigAccount::load_unpaused(program_id, config_account_info)?;
+
+    if side > OrderSide::BuyNo as u8 {
+        msg!("order side unknown");
+        return Err(SearchMarketError::InvalidOrderSide.into());
+    }
     let side: OrderSide = side.into();
+
+    if quantity < config.params.min_order_quantity {
+        msg!(
+            "order below minimum of {}",
+            config.params.min_order_quantity
+        );
+        return Err(SearchMarketError::OrderTooSmall.into());
+    }
+
+    if !owner_account_info.is_signer {
+        msg!("order owner did not sign");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
     let clock = Clock::get()?;
     let rent = Rent::get()?;
 
     if *spl_token_program_info.key != spl_token::id() {
         msg!("spl token program id incorrect");
-        return Err(ProgramError::InvalidArgument);
+        return Err(SearchMarketError::InvalidTokenProgram.into());
     }
 
     if *rent_account_info.key != rent::id() {
         msg!("rent id incorrect");
-        return Err(ProgramError::InvalidArgument);
+        return Err(SearchMarketError::InvalidRentSysvar.into());
+    }
+
+    if *system_program_info.key != system_program::id() {
+        msg!("system program id incorrect");
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let order_seeds: &[&[u8]] = &[
+        b"order",
+        &owner_account_info.key.to_bytes(),
+        &client_id.to_le_bytes(),
+        &[order_bump_seed],
+    ];
+    if Pubkey::create_program_address(order_seeds, program_id)? != *order_account_info.key {
+        msg!("order account key incorrect");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    if fee_vault_address(program_id, &referrer, fee_vault_bump_seed)? != *fee_vault_account_info.key
+    {
+        msg!("fee vault key incorrect");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
     }
 
+    if *market_account_info.owner != *program_id
+        || *result_account_info.owner != *program_id
+        || *book_account_info.owner != *program_id
+    {
+        msg!("market, result or book not owned by program");
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+    if market.state(clock.slot)? != MarketState::Open {
+        msg!("trading closed at slot {}", market.expires_slot);
+        return Err(SearchMarketError::TradingClosed.into());
+    }
+    if !market.valid_price(price) {
+        msg!("price {} invalid for tick {}", price, market.tick_size);
+        return Err(SearchMarketError::InvalidPrice.into());
+    }
+    let mut result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.search_market != *market_account_info.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+    let order_mint = match side {
+        OrderSide::Buy | OrderSide::Sell => result.yes_mint,
+        OrderSide::BuyNo => result.no_mint,
+    };
+    if order_mint != *token_mint_account_info.key {
+        msg!("token mint does not match order side");
+        return Err(SearchMarketError::MintMismatch.into());
+    }
+    if side != OrderSide::Sell && !accepts_tokens(token_account_info, &order_mint) {
+        msg!("buy token account cannot receive its tokens");
+        return Err(SearchMarketError::InvalidTokenAccount.into());
+    }
+    let mut book = OrderBookAccount::unpack(&book_account_info.data.borrow())?;
+    if book.result != *result_account_info.key {
+        msg!("book does not belong to result");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    let mut pair_mint = PairMint::new(
+        program_id,
+        market_account_info.key,
+        &market,
+        &result,
+        vault_account_info,
+        yes_mint_account_info,
+        no_mint_account_info,
+    )?;
+
+    let book_price = side.book_price(price)?;
+    let (filled, _cost, _fees) = match_resting_orders(
+        program_id,
+        &mut book,
+        side,
+        book_price,
+        quantity,
+        owner_account_info.key,
+        sol_account_info,
+        token_account_info,
+        token_authority_account_info,
+        fee_vault_account_info,
+        spl_token_program_info,
+        system_program_info,
+        config.params.taker_fee_bps,
+        &mut pair_mint,
+        account_info_iter,
+    )?;
+    pair_mint.record_collateral(&mut result, result_account_info)?;
+    let remaining = quantity
+        .checked_sub(filled)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if remaining == 0 {
+        msg!("Order filled against the book");
+        return book
+            .serialize(&mut &mut book_account_info.data.borrow_mut()[..])
+            .map_err(|e| e.into());
+    }
+    if let Some(best) = book.best(side.opposite()) {
+        if side.crosses(book_price, best.price) {
+            msg!("order would rest against crossing liquidity");
+            return Err(SearchMarketError::MakerAccountsMissing.into());
+        }
+    }
+    book.insert(side, *order_account_info.key, book_price, remaining)?;
+    book.serialize(&mut &mut book_account_info.data.borrow_mut()[..])?;
+
     match side {
-        OrderSide::Buy => {
+        OrderSide::Buy | OrderSide::BuyNo => {
             let escrow_pubkey = Pubkey::create_program_address(
                 &[
                     b"sol_escrow",
@@ -176,14 +445,16 @@ pub fn create_order(
             .unwrap();
             if *escrow_account_info.key != escrow_pubkey {
                 msg!("sol escrow account key incorrect");
-                return Err(ProgramError::InvalidAccountData);
+                return Err(SearchMarketError::EscrowMismatch.into());
             }
 
             invoke(
                 &system_instruction::transfer(
                     sol_account_info.key,
                     &escrow_account_info.key,
-                    price * quantity + rent.minimum_balance(0),
+                    buy_escrow_amount(price, remaining, config.params.maker_fee_bps)?
+                        .checked_add(rent.minimum_balance(0))
+                        .ok_or(SearchMarketError::ArithmeticOverflow)?,
                 ),
                 &[
                     sol_account_info.clone(),
@@ -201,27 +472,18 @@ pub fn create_order(
             let escrow_pubkey = Pubkey::create_program_address(escrow_seeds, program_id).unwrap();
             if *escrow_account_info.key != escrow_pubkey {
                 msg!("token escrow account key incorrect");
-                return Err(ProgramError::InvalidAccountData);
+                return Err(SearchMarketError::EscrowMismatch.into());
             }
 
             msg!("Create escrow");
-            invoke_signed(
-                &system_instruction::create_account(
-                    sol_account_info.key,
-                    escrow_account_info.key,
-                    rent.minimum_balance(spl_token::state::Account::LEN),
-                    spl_token::state::Account::LEN as u64,
-                    &spl_token::id(),
-                ),
-                &[
-                    sol_account_info.clone(),
-                    escrow_account_info.clone(),
-                    spl_token_program_info.clone(),
-                    system_program_info.clone(),
-                ],
-                &[escrow_seeds],
-            )
-            .unwrap();
+            create_pda_account(
+                sol_account_info,
+                escrow_account_info,
+                system_program_info,
+                Account::LEN,
+                &spl_token::id(),
+                escrow_seeds,
+            )?;
 
             msg!("Initialize Escrow");
             invoke(
@@ -248,7 +510,7 @@ pub fn create_order(
                     &escrow_pubkey,
                     &token_authority_account_info.key,
                     &[],
-                    quantity,
+                    remaining,
                 )
                 .unwrap(),
                 &[
@@ -261,24 +523,370 @@ pub fn create_order(
         }
     }
 
-    let order = OrderAccount::new(
+    let mut order = OrderAccount::new(
         *market_account_info.key,
         *result_account_info.key,
+        *owner_account_info.key,
         *sol_account_info.key,
         *token_account_info.key,
         side,
         price,
-        quantity,
+        remaining,
         escrow_bump_seed,
         clock.slot,
         *execution_authority_account_info.key,
     );
-    msg!("Writing Order to Ledger {:?}", order);
+    order.referrer = referrer;
+    order.fee_vault_bump_seed = fee_vault_bump_seed;
+    order.maker_fee_bps = config.params.maker_fee_bps;
+
+    let data = order.try_to_vec()?;
+    create_pda_account(
+        sol_account_info,
+        order_account_info,
+        system_program_info,
+        data.len(),
+        program_id,
+        order_seeds,
+    )?;
+    order_account_info.data.borrow_mut().copy_from_slice(&data);
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_order_escrow(&order, escrow_account_info)?;
+
+    SearchMarketEvent::OrderPlaced {
+        result: order.result,
+        order: *order_account_info.key,
+        owner: order.owner,
+        side: order.side,
+        price: order.price,
+        quantity: order.quantity,
+    }
+    .emit()
+}
+
+/// Whether `token_account` can be paid tokens of `mint`: an initialized SPL token account of that
+/// mint that is not frozen. A buy is only paid once it fills, so this is checked when it is placed
+/// and again whenever it is matched.
+fn accepts_tokens(token_account: &AccountInfo, mint: &Pubkey) -> bool {
+    *token_account.owner == spl_token::id()
+        && Account::unpack(&token_account.data.borrow())
+            .map(|account| account.mint == *mint && !account.is_frozen())
+            .unwrap_or(false)
+}
+
+/// Lamports a resting buy escrows on top of rent: its notional plus the most it can owe in maker
+/// fees. Fees are rounded down per fill, so partial fills never draw more than this reserve.
+pub(crate) fn buy_escrow_amount(
+    price: u64,
+    quantity: u64,
+    maker_fee_bps: u16,
+) -> Result<u64, SearchMarketError> {
+    let notional = price
+        .checked_mul(quantity)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    notional
+        .checked_add(fee_amount(notional, maker_fee_bps)?)
+        .ok_or(SearchMarketError::ArithmeticOverflow)
+}
+
+fn transfer_lamports<'a>(
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    amount: u64,
+    system_program: &AccountInfo<'a>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    if amount == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::transfer(source.key, destination.key, amount),
+        &[source.clone(), destination.clone(), system_program.clone()],
+        signer_seeds,
+    )
+}
+
+/// Trades an incoming order at book price `price` against crossing resting orders at their prices,
+/// best first, reading each maker's accounts from `maker_accounts` until they run out. `taker` owns
+/// the incoming order and is reported on each fill. Returns the quantity filled, the lamports the
+/// taker traded for and the taker fees paid into `fee_vault`.
+///
+/// Every trade settles at the resting order's price: an incoming buy pays each maker its ask out of
+/// `sol_account` rather than escrowing at its own limit, and a resting buy's escrow is drawn down by
+/// exactly `price * quantity` filled plus its maker fee, so it always holds its remaining notional,
+/// fee reserve and rent. Fees come out of the lamports leg: a buyer pays them on top of the price
+/// and a seller receives the price less them. When a buy meets a buy of no tokens, both pay the
+/// vault instead and `pair_mint` mints each its side of new pairs.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn match_resting_orders<'a, 'b: 'a>(
+    program_id: &Pubkey,
+    book: &mut OrderBookAccount,
+    side: OrderSide,
+    price: u64,
+    quantity: u64,
+    taker: &Pubkey,
+    sol_account_info: &AccountInfo<'b>,
+    token_account_info: &AccountInfo<'b>,
+    token_authority_account_info: &AccountInfo<'b>,
+    fee_vault_account_info: &AccountInfo<'b>,
+    spl_token_program_info: &AccountInfo<'b>,
+    system_program_info: &AccountInfo<'b>,
+    taker_fee_bps: u16,
+    pair_mint: &mut PairMint<'_, 'b>,
+    maker_accounts: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<(u64, u64, u64), ProgramError> {
+    let book_side = side.opposite();
+    let mut remaining = quantity;
+    let mut total_cost: u64 = 0;
+    let mut total_fees: u64 = 0;
+    while remaining > 0 && maker_accounts.len() > 0 {
+        let best_order = match book.best(book_side) {
+            Some(best) if side.crosses(price, best.price) => best.order,
+            _ => break,
+        };
+
+        let mut next_maker_account = || {
+            next_account_info(maker_accounts)
+                .map_err(|_| ProgramError::from(SearchMarketError::MakerAccountsMissing))
+        };
+        let maker_order_info = next_maker_account()?;
+        let maker_escrow_info = next_maker_account()?;
+        let maker_sol_account_info = next_maker_account()?;
+        let maker_token_account_info = next_maker_account()?;
+        let maker_fee_vault_info = next_maker_account()?;
+
+        if *maker_order_info.key != best_order {
+            msg!("maker accounts not in book order");
+            return Err(SearchMarketError::MakerAccountsMissing.into());
+        }
+        if *maker_order_info.owner != *program_id {
+            return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+        }
+        let mut maker = OrderAccount::try_from_slice(&maker_order_info.data.borrow())?;
+        let maker_side: OrderSide = maker.side.into();
+        if *maker_sol_account_info.key != maker.sol_account
+            || *maker_token_account_info.key != maker.token_account
+        {
+            return Err(SearchMarketError::OrderAccountMismatch.into());
+        }
+        let maker_escrow_seeds: &[&[u8]] = &[
+            escrow_name(maker_side),
+            &maker_order_info.key.to_bytes(),
+            &[maker.escrow_bump_seed],
+        ];
+        if Pubkey::create_program_address(maker_escrow_seeds, program_id)? != *maker_escrow_info.key
+        {
+            return Err(SearchMarketError::EscrowMismatch.into());
+        }
+        if fee_vault_address(program_id, &maker.referrer, maker.fee_vault_bump_seed)?
+            != *maker_fee_vault_info.key
+        {
+            return Err(SearchMarketError::InvalidAccountAddress.into());
+        }
+
+        if maker_side != OrderSide::Sell
+            && !accepts_tokens(maker_token_account_info, pair_mint.buy_mint(maker_side))
+        {
+            // A buy whose token account was closed or frozen can never fill. Refund and drop it
+            // rather than failing every taker that reaches it.
+            msg!("Evicting unfillable order {}", maker_order_info.key);
+            book.remove(book_side, maker_order_info.key)?;
+            close_order(
+                maker_side,
+                maker_order_info,
+                maker_escrow_info,
+                maker_sol_account_info,
+                maker_escrow_seeds,
+                spl_token_program_info,
+                system_program_info,
+            )?;
+            SearchMarketEvent::OrderCancelled {
+                result: maker.result,
+                order: *maker_order_info.key,
+            }
+            .emit()?;
+            continue;
+        }
+
+        let fill_quantity = remaining.min(maker.quantity);
+        let maker_cost = maker
+            .price
+            .checked_mul(fill_quantity)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        let cost = match (side, maker_side) {
+            (OrderSide::Buy, OrderSide::Sell) | (OrderSide::Sell, OrderSide::Buy) => maker_cost,
+            _ => LAMPORTS_PER_TOKEN
+                .checked_mul(fill_quantity)
+                .and_then(|collateral| collateral.checked_sub(maker_cost))
+                .ok_or(SearchMarketError::ArithmeticOverflow)?,
+        };
+        let taker_fee = fee_amount(cost, taker_fee_bps)?;
+        let maker_fee = fee_amount(maker_cost, maker.maker_fee_bps)?;
+        match (side, maker_side) {
+            (OrderSide::Buy, OrderSide::Sell) => {
+                transfer_lamports(
+                    sol_account_info,
+                    maker_sol_account_info,
+                    cost.checked_sub(maker_fee)
+                        .ok_or(SearchMarketError::ArithmeticOverflow)?,
+                    system_program_info,
+                    &[],
+                )?;
+                transfer_lamports(
+                    sol_account_info,
+                    maker_fee_vault_info,
+                    maker_fee,
+                    system_program_info,
+                    &[],
+                )?;
+                transfer_lamports(
+                    sol_account_info,
+                    fee_vault_account_info,
+                    taker_fee,
+                    system_program_info,
+                    &[],
+                )?;
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        maker_escrow_info.key,
+                        token_account_info.key,
+                        maker_escrow_info.key,
+                        &[],
+                        fill_quantity,
+                    )?,
+                    &[
+                        maker_escrow_info.clone(),
+                        token_account_info.clone(),
+                        maker_escrow_info.clone(),
+                        spl_token_program_info.clone(),
+                    ],
+                    &[maker_escrow_seeds],
+                )?;
+            }
+            (OrderSide::Sell, OrderSide::Buy) => {
+                invoke(
+                    &spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        token_account_info.key,
+                        maker_token_account_info.key,
+                        token_authority_account_info.key,
+                        &[],
+                        fill_quantity,
+                    )?,
+                    &[
+                        token_account_info.clone(),
+                        maker_token_account_info.clone(),
+                        token_authority_account_info.clone(),
+                        spl_token_program_info.clone(),
+                    ],
+                )?;
+                transfer_lamports(
+                    maker_escrow_info,
+                    sol_account_info,
+                    cost.checked_sub(taker_fee)
+                        .ok_or(SearchMarketError::ArithmeticOverflow)?,
+                    system_program_info,
+                    &[maker_escrow_seeds],
+                )?;
+                transfer_lamports(
+                    maker_escrow_info,
+                    fee_vault_account_info,
+                    taker_fee,
+                    system_program_info,
+                    &[maker_escrow_seeds],
+                )?;
+                transfer_lamports(
+                    maker_escrow_info,
+                    maker_fee_vault_info,
+                    maker_fee,
+                    system_program_info,
+                    &[maker_escrow_seeds],
+                )?;
+            }
+            _ => {
+                transfer_lamports(
+                    sol_account_info,
+                    pair_mint.vault,
+                    cost,
+                    system_program_info,
+                    &[],
+                )?;
+                transfer_lamports(
+                    sol_account_info,
+                    fee_vault_account_info,
+                    taker_fee,
+                    system_program_info,
+                    &[],
+                )?;
+                transfer_lamports(
+                    maker_escrow_info,
+                    pair_mint.vault,
+                    maker_cost,
+                    system_program_info,
+                    &[maker_escrow_seeds],
+                )?;
+                transfer_lamports(
+                    maker_escrow_info,
+                    maker_fee_vault_info,
+                    maker_fee,
+                    system_program_info,
+                    &[maker_escrow_seeds],
+                )?;
+                let (yes_token_info, no_token_info) = if side == OrderSide::Buy {
+                    (token_account_info, maker_token_account_info)
+                } else {
+                    (maker_token_account_info, token_account_info)
+                };
+                pair_mint.mint(
+                    yes_token_info,
+                    no_token_info,
+                    spl_token_program_info,
+                    fill_quantity,
+                )?;
+            }
+        }
 
-    order
-        .serialize(&mut &mut order_account_info.data.borrow_mut()[..])
-        .map(|_| ())
-        .map_err(|e| e.into())
+        book.fill(book_side, maker_order_info.key, fill_quantity)?;
+        SearchMarketEvent::OrderFilled {
+            result: maker.result,
+            maker_order: *maker_order_info.key,
+            taker: *taker,
+            side: maker.side,
+            price: maker.price,
+            quantity: fill_quantity,
+        }
+        .emit()?;
+        maker.quantity = maker
+            .quantity
+            .checked_sub(fill_quantity)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        if maker.quantity == 0 {
+            close_order(
+                maker_side,
+                maker_order_info,
+                maker_escrow_info,
+                maker_sol_account_info,
+                maker_escrow_seeds,
+                spl_token_program_info,
+                system_program_info,
+            )?;
+        } else {
+            maker.serialize(&mut &mut maker_order_info.data.borrow_mut()[..])?;
+
+            #[cfg(feature = "debug-invariants")]
+            super::check_order_escrow(&maker, maker_escrow_info)?;
+        }
+        remaining -= fill_quantity;
+        total_cost = total_cost
+            .checked_add(cost)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        total_fees = total_fees
+            .checked_add(taker_fee)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    }
+    Ok((quantity - remaining, total_cost, total_fees))
 }
 
 #[cfg(test)]
@@ -288,14 +896,16 @@ pub mod test {
     use crate::instructions::test_utils::*;
     use crate::process_instruction;
     use crate::test_utils::*;
-    use crate::ResultAccount;
+    use crate::{fill_order_instruction, BookEntry, ResultAccount};
     use solana_program::program_pack::Pack;
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::{
         account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
         rent::Rent,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
     use spl_token::state::Account;
 
@@ -303,12 +913,11 @@ pub mod test {
         order: &mut OrderAccount,
         token_mint_account: &Pubkey,
         token_authority_account: &Pubkey,
-        program_test: &mut ProgramTest,
         program_id: &Pubkey,
     ) -> (Pubkey, Pubkey, Instruction) {
-        let order_key = Pubkey::new_unique();
+        let (order_key, _order_bump_seed) = find_order_address(program_id, &order.owner, 0);
         let (escrow_key, bump_seed) = match order.side.into() {
-            OrderSide::Buy => {
+            OrderSide::Buy | OrderSide::BuyNo => {
                 Pubkey::find_program_address(&[b"sol_escrow", &order_key.to_bytes()], &program_id)
             }
             OrderSide::Sell => {
@@ -316,14 +925,13 @@ pub mod test {
             }
         };
         order.escrow_bump_seed = bump_seed;
+        order.fee_vault_bump_seed =
+            find_fee_vault_address(program_id, &order.referrer.unwrap_or_default()).1;
 
-        let order_space = space(order).unwrap();
-        let order_min_balance = minimum_balance(order).unwrap();
-        let order_account = SolanaAccount::new(order_min_balance, order_space, &program_id);
-        program_test.add_account(order_key, order_account);
         let create_order = create_order_instruction(
             &program_id,
-            &order_key,
+            0,
+            &order.owner,
             &order.search_market,
             &order.result,
             &order.sol_account,
@@ -331,9 +939,11 @@ pub mod test {
             token_mint_account,
             token_authority_account,
             &order.execution_authority,
+            order.referrer,
             order.side.into(),
             order.price,
             order.quantity,
+            &[],
         )
         .unwrap();
 
@@ -347,9 +957,15 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -358,9 +974,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let deposit_keypair = Keypair::new();
         let (yes_token_pubkey, init_yes_token) = setup_token(
@@ -388,17 +1004,12 @@ pub mod test {
         );
 
         let sol_account_keypair = Keypair::new();
-        let sol_account = SolanaAccount::new(
-            Rent::default().minimum_balance(Account::LEN),
-            0,
-            &system_program::id(),
-        );
-        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
 
         let mut order = OrderAccount::new(
             market_key,
             result_key,
             sol_account_keypair.pubkey(),
+            sol_account_keypair.pubkey(),
             yes_token_pubkey,
             OrderSide::Sell,
             500,
@@ -411,10 +1022,16 @@ pub mod test {
             &mut order,
             &result.yes_mint,
             &deposit_keypair.pubkey(),
-            &mut program_test,
             &program_id,
         );
 
+        let sol_account = SolanaAccount::new(
+            Rent::default().minimum_balance(Account::LEN) + minimum_balance(&order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let mut setup_transaction = Transaction::new_with_payer(
             &[
@@ -453,6 +1070,24 @@ pub mod test {
             .unwrap();
         let yes_token_data = Account::unpack_from_slice(&yes_token_account.data).unwrap();
         assert_eq!(yes_token_data.amount, 0);
+
+        let escrow_account = banks_client.get_account(escrow_key).await.unwrap().unwrap();
+        let escrow_data = Account::unpack_from_slice(&escrow_account.data).unwrap();
+        assert_eq!(escrow_data.amount, 100);
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(
+            book.asks,
+            vec![BookEntry {
+                order: order_key,
+                price: 500,
+                quantity: 100,
+                sequence: 0,
+            }]
+        );
+        assert_eq!(book.bids, vec![]);
     }
 
     #[tokio::test]
@@ -462,9 +1097,15 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -473,9 +1114,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let deposit_keypair = Keypair::new();
         let (yes_token_pubkey, init_yes_token) = setup_token(
@@ -503,16 +1144,11 @@ pub mod test {
         );
 
         let sol_account_keypair = Keypair::new();
-        let sol_account = SolanaAccount::new(
-            2 * Rent::default().minimum_balance(0) + 500 * 100,
-            0,
-            &system_program::id(),
-        );
-        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
         let mut order = OrderAccount::new(
             market_key,
             result_key,
             sol_account_keypair.pubkey(),
+            sol_account_keypair.pubkey(),
             yes_token_pubkey,
             OrderSide::Buy,
             500,
@@ -525,10 +1161,16 @@ pub mod test {
             &mut order,
             &result.yes_mint,
             &deposit_keypair.pubkey(),
-            &mut program_test,
             &program_id,
         );
 
+        let sol_account = SolanaAccount::new(
+            2 * Rent::default().minimum_balance(0) + 500 * 100 + minimum_balance(&order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let mut setup_transaction = Transaction::new_with_payer(
             &[
@@ -573,4 +1215,809 @@ pub mod test {
             .unwrap();
         assert_eq!(Rent::default().minimum_balance(0), sol_account.lamports);
     }
+
+    #[tokio::test]
+    async fn test_create_order_invalid_price() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let sol_account_keypair = Keypair::new();
+        let mut order = OrderAccount::new(
+            market_key,
+            result_key,
+            sol_account_keypair.pubkey(),
+            sol_account_keypair.pubkey(),
+            yes_token_pubkey,
+            OrderSide::Buy,
+            505,
+            100,
+            0,
+            1,
+            deposit_keypair.pubkey(),
+        );
+        let (order_key, escrow_key, create_order) = setup_order(
+            &mut order,
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &program_id,
+        );
+
+        let sol_account = SolanaAccount::new(
+            2 * Rent::default().minimum_balance(0) + 505 * 100 + minimum_balance(&order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(sol_account_keypair.pubkey(), sol_account);
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut setup_transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                deposit_instruction,
+            ],
+            Some(&payer.pubkey()),
+        );
+        setup_transaction.sign(
+            &[&payer, &decision_authority, &deposit_keypair],
+            recent_blockhash,
+        );
+        banks_client
+            .process_transaction(setup_transaction)
+            .await
+            .unwrap();
+
+        let mut transaction = Transaction::new_with_payer(&[create_order], Some(&payer.pubkey()));
+        transaction.sign(
+            &[&payer, &deposit_keypair, &sol_account_keypair],
+            recent_blockhash,
+        );
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidPrice as u32)
+            )
+        );
+        assert!(banks_client.get_account(order_key).await.unwrap().is_none());
+        assert!(banks_client
+            .get_account(escrow_key)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_order_matches_book() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let seller_sol_keypair = Keypair::new();
+        let mut sell_order = OrderAccount::new(
+            market_key,
+            result_key,
+            seller_sol_keypair.pubkey(),
+            seller_sol_keypair.pubkey(),
+            yes_token_pubkey,
+            OrderSide::Sell,
+            500,
+            100,
+            0,
+            1,
+            deposit_keypair.pubkey(),
+        );
+        let (sell_order_key, sell_escrow_key, create_sell_order) = setup_order(
+            &mut sell_order,
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &program_id,
+        );
+        let seller_sol_account = SolanaAccount::new(
+            Rent::default().minimum_balance(Account::LEN) + minimum_balance(&sell_order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(seller_sol_keypair.pubkey(), seller_sol_account);
+
+        let buyer_keypair = Keypair::new();
+        let (buyer_token_pubkey, init_buyer_token) =
+            setup_token(&result.yes_mint, &buyer_keypair.pubkey(), &mut program_test);
+        let buyer_sol_account = SolanaAccount::new(
+            Rent::default().minimum_balance(0) + 500 * 100 + 90 + 60,
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(buyer_keypair.pubkey(), buyer_sol_account);
+
+        let makers = [(sell_order_key, sell_order.clone())];
+        let partial_buy = create_order_instruction(
+            &program_id,
+            0,
+            &buyer_keypair.pubkey(),
+            &market_key,
+            &result_key,
+            &buyer_keypair.pubkey(),
+            &buyer_token_pubkey,
+            &result.yes_mint,
+            &buyer_keypair.pubkey(),
+            &buyer_keypair.pubkey(),
+            None,
+            OrderSide::Buy,
+            600,
+            60,
+            &makers,
+        )
+        .unwrap();
+        let remaining_buy = create_order_instruction(
+            &program_id,
+            1,
+            &buyer_keypair.pubkey(),
+            &market_key,
+            &result_key,
+            &buyer_keypair.pubkey(),
+            &buyer_token_pubkey,
+            &result.yes_mint,
+            &buyer_keypair.pubkey(),
+            &buyer_keypair.pubkey(),
+            None,
+            OrderSide::Buy,
+            500,
+            40,
+            &makers,
+        )
+        .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut setup_transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                init_buyer_token,
+                deposit_instruction,
+                create_sell_order,
+            ],
+            Some(&payer.pubkey()),
+        );
+        setup_transaction.sign(
+            &[
+                &payer,
+                &decision_authority,
+                &deposit_keypair,
+                &seller_sol_keypair,
+            ],
+            recent_blockhash,
+        );
+        banks_client
+            .process_transaction(setup_transaction)
+            .await
+            .unwrap();
+
+        let mut transaction = Transaction::new_with_payer(&[partial_buy], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &buyer_keypair], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let sell_order_account = banks_client
+            .get_account(sell_order_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let processed_sell_order = OrderAccount::try_from_slice(&sell_order_account.data).unwrap();
+        assert_eq!(processed_sell_order.quantity, 40);
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.asks[0].quantity, 40);
+        assert_eq!(book.bids, vec![]);
+
+        let buyer_order_key = find_order_address(&program_id, &buyer_keypair.pubkey(), 0).0;
+        assert_eq!(
+            banks_client.get_account(buyer_order_key).await.unwrap(),
+            None
+        );
+
+        let seller_sol_account = banks_client
+            .get_account(seller_sol_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(seller_sol_account.lamports, 500 * 60);
+
+        let mut transaction = Transaction::new_with_payer(&[remaining_buy], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &buyer_keypair], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert_eq!(
+            banks_client.get_account(sell_order_key).await.unwrap(),
+            None
+        );
+        assert_eq!(
+            banks_client.get_account(sell_escrow_key).await.unwrap(),
+            None
+        );
+
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.asks, vec![]);
+
+        let buyer_token_account = banks_client
+            .get_account(buyer_token_pubkey)
+            .await
+            .unwrap()
+            .unwrap();
+        let buyer_token = Account::unpack_from_slice(&buyer_token_account.data).unwrap();
+        assert_eq!(buyer_token.amount, 100);
+
+        let seller_sol_account = banks_client
+            .get_account(seller_sol_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            seller_sol_account.lamports,
+            500 * 100
+                + Rent::default().minimum_balance(Account::LEN)
+                + minimum_balance(&sell_order).unwrap()
+        );
+
+        let buyer_sol_account = banks_client
+            .get_account(buyer_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            buyer_sol_account.lamports,
+            Rent::default().minimum_balance(0)
+        );
+
+        let (fee_vault_key, _fee_vault_bump_seed) =
+            find_fee_vault_address(&program_id, &Pubkey::default());
+        let fee_vault_account = banks_client
+            .get_account(fee_vault_key)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee_vault_account.lamports, 90 + 60);
+    }
+
+    #[tokio::test]
+    async fn test_complementary_buys_mint_pairs() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let yes_bidder = Keypair::new();
+        let (yes_bidder_token, init_yes_bidder_token) =
+            setup_token(&result.yes_mint, &yes_bidder.pubkey(), &mut program_test);
+        program_test.add_account(
+            yes_bidder.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let mut yes_bid = OrderAccount::new(
+            market_key,
+            result_key,
+            yes_bidder.pubkey(),
+            yes_bidder.pubkey(),
+            yes_bidder_token,
+            OrderSide::Buy,
+            60_000,
+            10,
+            0,
+            1,
+            yes_bidder.pubkey(),
+        );
+        let (yes_bid_key, _escrow_key, create_yes_bid) = setup_order(
+            &mut yes_bid,
+            &result.yes_mint,
+            &yes_bidder.pubkey(),
+            &program_id,
+        );
+
+        let no_taker = Keypair::new();
+        let (no_taker_token, init_no_taker_token) =
+            setup_token(&result.no_mint, &no_taker.pubkey(), &mut program_test);
+        program_test.add_account(
+            no_taker.pubkey(),
+            SolanaAccount::new(
+                Rent::default().minimum_balance(0) + 40_000 * 10 + 1_200,
+                0,
+                &system_program::id(),
+            ),
+        );
+        let take_no = create_order_instruction(
+            &program_id,
+            0,
+            &no_taker.pubkey(),
+            &market_key,
+            &result_key,
+            &no_taker.pubkey(),
+            &no_taker_token,
+            &result.no_mint,
+            &no_taker.pubkey(),
+            &no_taker.pubkey(),
+            None,
+            OrderSide::BuyNo,
+            40_000,
+            10,
+            &[(yes_bid_key, yes_bid)],
+        )
+        .unwrap();
+
+        let no_bidder = Keypair::new();
+        let (no_bidder_token, init_no_bidder_token) =
+            setup_token(&result.no_mint, &no_bidder.pubkey(), &mut program_test);
+        program_test.add_account(
+            no_bidder.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let mut no_bid = OrderAccount::new(
+            market_key,
+            result_key,
+            no_bidder.pubkey(),
+            no_bidder.pubkey(),
+            no_bidder_token,
+            OrderSide::BuyNo,
+            30_000,
+            5,
+            0,
+            1,
+            no_bidder.pubkey(),
+        );
+        let (no_bid_key, _escrow_key, create_no_bid) = setup_order(
+            &mut no_bid,
+            &result.no_mint,
+            &no_bidder.pubkey(),
+            &program_id,
+        );
+
+        let yes_taker = Keypair::new();
+        let (yes_taker_token, init_yes_taker_token) =
+            setup_token(&result.yes_mint, &yes_taker.pubkey(), &mut program_test);
+        program_test.add_account(
+            yes_taker.pubkey(),
+            SolanaAccount::new(
+                Rent::default().minimum_balance(0) + 70_000 * 5 + 1_050,
+                0,
+                &system_program::id(),
+            ),
+        );
+        let take_yes = fill_order_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &yes_taker.pubkey(),
+            &yes_taker_token,
+            &yes_taker.pubkey(),
+            None,
+            OrderSide::Buy,
+            70_000,
+            5,
+            &[(no_bid_key, no_bid)],
+        )
+        .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut setup_transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_bidder_token,
+                init_no_taker_token,
+                init_no_bidder_token,
+                init_yes_taker_token,
+                create_yes_bid,
+            ],
+            Some(&payer.pubkey()),
+        );
+        setup_transaction.sign(
+            &[&payer, &decision_authority, &yes_bidder],
+            recent_blockhash,
+        );
+        banks_client
+            .process_transaction(setup_transaction)
+            .await
+            .unwrap();
+
+        let mut transaction = Transaction::new_with_payer(&[take_no], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &no_taker], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let mut transaction = Transaction::new_with_payer(&[create_no_bid], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &no_bidder], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.bids, vec![]);
+        assert_eq!(book.asks[0].price, 70_000);
+
+        let mut transaction = Transaction::new_with_payer(&[take_yes], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &yes_taker], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        for (token, amount) in [
+            (yes_bidder_token, 10),
+            (no_taker_token, 10),
+            (no_bidder_token, 5),
+            (yes_taker_token, 5),
+        ] {
+            let token_account = banks_client.get_account(token).await.unwrap().unwrap();
+            assert_eq!(
+                Account::unpack_from_slice(&token_account.data)
+                    .unwrap()
+                    .amount,
+                amount
+            );
+        }
+        for taker in [no_taker.pubkey(), yes_taker.pubkey()] {
+            let taker_account = banks_client.get_account(taker).await.unwrap().unwrap();
+            assert_eq!(taker_account.lamports, Rent::default().minimum_balance(0));
+        }
+        for order in [yes_bid_key, no_bid_key] {
+            assert_eq!(banks_client.get_account(order).await.unwrap(), None);
+        }
+
+        let result_account = banks_client.get_account(result_key).await.unwrap().unwrap();
+        let processed_result = ResultAccount::try_from_slice(&result_account.data).unwrap();
+        assert_eq!(processed_result.collateral, 15 * LAMPORTS_PER_TOKEN);
+        let (vault_key, _vault_bump_seed) = find_vault_address(&program_id, &market_key);
+        let vault_account = banks_client.get_account(vault_key).await.unwrap().unwrap();
+        assert_eq!(
+            vault_account.lamports,
+            Rent::default().minimum_balance(0) + 15 * LAMPORTS_PER_TOKEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_evicts_unfillable_buy() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+
+        let buyer_keypair = Keypair::new();
+        let (buyer_token_pubkey, init_buyer_token) =
+            setup_token(&result.yes_mint, &buyer_keypair.pubkey(), &mut program_test);
+        let mut buy_order = OrderAccount::new(
+            market_key,
+            result_key,
+            buyer_keypair.pubkey(),
+            buyer_keypair.pubkey(),
+            buyer_token_pubkey,
+            OrderSide::Buy,
+            500,
+            10,
+            0,
+            1,
+            buyer_keypair.pubkey(),
+        );
+        let (buy_order_key, buy_escrow_key, create_buy_order) = setup_order(
+            &mut buy_order,
+            &result.yes_mint,
+            &buyer_keypair.pubkey(),
+            &program_id,
+        );
+        let buyer_lamports = 2 * Rent::default().minimum_balance(0)
+            + 500 * 10
+            + minimum_balance(&buy_order).unwrap();
+        let buyer_sol_account = SolanaAccount::new(buyer_lamports, 0, &system_program::id());
+        program_test.add_account(buyer_keypair.pubkey(), buyer_sol_account);
+
+        let buy_instruction = |client_id, token_account, quantity| {
+            create_order_instruction(
+                &program_id,
+                client_id,
+                &buyer_keypair.pubkey(),
+                &market_key,
+                &result_key,
+                &buyer_keypair.pubkey(),
+                token_account,
+                &result.yes_mint,
+                &buyer_keypair.pubkey(),
+                &buyer_keypair.pubkey(),
+                None,
+                OrderSide::Buy,
+                500,
+                quantity,
+                &[],
+            )
+            .unwrap()
+        };
+        let dust_buy = buy_instruction(1, &buyer_token_pubkey, 4);
+        let fake_token_buy = buy_instruction(2, &Pubkey::new_unique(), 10);
+
+        let seller_sol_keypair = Keypair::new();
+        let sell_order = OrderAccount::new(
+            market_key,
+            result_key,
+            seller_sol_keypair.pubkey(),
+            seller_sol_keypair.pubkey(),
+            yes_token_pubkey,
+            OrderSide::Sell,
+            500,
+            10,
+            0,
+            1,
+            deposit_keypair.pubkey(),
+        );
+        let seller_sol_account = SolanaAccount::new(
+            Rent::default().minimum_balance(Account::LEN) + minimum_balance(&sell_order).unwrap(),
+            0,
+            &system_program::id(),
+        );
+        program_test.add_account(seller_sol_keypair.pubkey(), seller_sol_account);
+        let create_sell_order = create_order_instruction(
+            &program_id,
+            0,
+            &seller_sol_keypair.pubkey(),
+            &market_key,
+            &result_key,
+            &seller_sol_keypair.pubkey(),
+            &yes_token_pubkey,
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &deposit_keypair.pubkey(),
+            None,
+            OrderSide::Sell,
+            500,
+            10,
+            &[(buy_order_key, buy_order.clone())],
+        )
+        .unwrap();
+        let close_buyer_token = spl_token::instruction::close_account(
+            &spl_token::id(),
+            &buyer_token_pubkey,
+            &buyer_keypair.pubkey(),
+            &buyer_keypair.pubkey(),
+            &[],
+        )
+        .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut setup_transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                init_buyer_token,
+                deposit_instruction,
+            ],
+            Some(&payer.pubkey()),
+        );
+        setup_transaction.sign(
+            &[&payer, &decision_authority, &deposit_keypair],
+            recent_blockhash,
+        );
+        banks_client
+            .process_transaction(setup_transaction)
+            .await
+            .unwrap();
+
+        for (instruction, error) in [
+            (dust_buy, SearchMarketError::OrderTooSmall),
+            (fake_token_buy, SearchMarketError::InvalidTokenAccount),
+        ] {
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+            transaction.sign(&[&payer, &buyer_keypair], recent_blockhash);
+            let transaction_error = banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                transaction_error.unwrap(),
+                TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+            );
+        }
+
+        // The buyer closes its token account once the order rests, so it can never be paid.
+        let mut transaction = Transaction::new_with_payer(
+            &[create_buy_order, close_buyer_token],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &buyer_keypair], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[create_sell_order], Some(&payer.pubkey()));
+        transaction.sign(
+            &[&payer, &seller_sol_keypair, &deposit_keypair],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert_eq!(banks_client.get_account(buy_order_key).await.unwrap(), None);
+        assert_eq!(
+            banks_client.get_account(buy_escrow_key).await.unwrap(),
+            None
+        );
+        let buyer_sol_account = banks_client
+            .get_account(buyer_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            buyer_sol_account.lamports,
+            buyer_lamports + Rent::default().minimum_balance(Account::LEN)
+        );
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.bids, vec![]);
+        assert_eq!(book.asks.len(), 1);
+        assert_eq!(book.asks[0].quantity, 10);
+    }
 }
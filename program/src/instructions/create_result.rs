@@ -1,5 +1,10 @@
-use super::{SearchMarketAccount, SearchMarketInstruction};
+use super::{
+    create_pda_account, find_book_address, find_config_address, find_vault_address, lmsr,
+    ConfigAccount, OrderBookAccount, SearchMarketAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
 use crate::instructions::AccountType;
+use crate::SearchMarketError;
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
@@ -9,37 +14,64 @@ use solana_program::{
     msg,
     program::invoke,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
+    system_program,
     sysvar::{rent, Sysvar},
 };
+use spl_token::state::Mint;
+
+pub fn find_result_address(program_id: &Pubkey, market: &Pubkey, index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"result", &market.to_bytes(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn find_yes_mint_address(program_id: &Pubkey, result: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"yes_mint", &result.to_bytes()], program_id)
+}
+
+pub fn find_no_mint_address(program_id: &Pubkey, result: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"no_mint", &result.to_bytes()], program_id)
+}
 
 pub fn create_result_instruction(
     program_id: &Pubkey,
-    result_pubkey: &Pubkey,
     market_pubkey: &Pubkey,
-    yes_mint_pubkey: &Pubkey,
-    no_mint_pubkey: &Pubkey,
+    index: u64,
+    payer_pubkey: &Pubkey,
     url: String,
     name: String,
     snippet: String,
 ) -> Result<Instruction, std::io::Error> {
-    let (mint_authority_key, bump_seed) =
-        Pubkey::find_program_address(&[b"mint_authority"], program_id);
+    let (result_pubkey, result_bump_seed) = find_result_address(program_id, market_pubkey, index);
+    let (yes_mint_pubkey, yes_mint_bump_seed) = find_yes_mint_address(program_id, &result_pubkey);
+    let (no_mint_pubkey, no_mint_bump_seed) = find_no_mint_address(program_id, &result_pubkey);
+    let (book_pubkey, book_bump_seed) = find_book_address(program_id, &result_pubkey);
+    let (vault_key, _bump_seed) = find_vault_address(program_id, market_pubkey);
     let data = SearchMarketInstruction::CreateResult {
         url,
         name,
         snippet,
-        bump_seed,
+        result_bump_seed,
+        yes_mint_bump_seed,
+        no_mint_bump_seed,
+        book_bump_seed,
     }
     .try_to_vec()?;
     let accounts = vec![
-        AccountMeta::new(*result_pubkey, false),
-        AccountMeta::new_readonly(*market_pubkey, false),
-        AccountMeta::new(*yes_mint_pubkey, false),
-        AccountMeta::new(*no_mint_pubkey, false),
-        AccountMeta::new_readonly(mint_authority_key, false),
+        AccountMeta::new(result_pubkey, false),
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(yes_mint_pubkey, false),
+        AccountMeta::new(no_mint_pubkey, false),
+        AccountMeta::new(book_pubkey, false),
+        AccountMeta::new_readonly(vault_key, false),
+        AccountMeta::new(*payer_pubkey, true),
         AccountMeta::new_readonly(rent::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
     ];
     Ok(Instruction {
         program_id: *program_id,
@@ -58,7 +90,19 @@ pub struct ResultAccount {
     pub snippet: String,
     pub yes_mint: Pubkey,
     pub no_mint: Pubkey,
-    pub bump_seed: u8,
+    pub collateral: u64,
+    /// Share of a token's collateral paid to yes tokens when the market was decided by weight.
+    pub payout_bps: u16,
+    /// Funded the result and its order book and gets their rent back when they are closed.
+    pub payer: Pubkey,
+    /// Yes tokens in excess of no tokens, left by `convert_no_to_yes` and backed by the market's
+    /// pooled collateral rather than this result's.
+    pub unpaired_yes: u64,
+    /// Liquidity of the result's market maker in tokens, zero if the market's subsidy had run out.
+    pub amm_liquidity: u64,
+    /// Net yes and no tokens the market maker has sold, negative once it has bought back more.
+    pub amm_yes: i64,
+    pub amm_no: i64,
 }
 
 impl ResultAccount {
@@ -69,7 +113,6 @@ impl ResultAccount {
         snippet: String,
         yes_mint: Pubkey,
         no_mint: Pubkey,
-        bump_seed: u8,
     ) -> ResultAccount {
         ResultAccount {
             account_type: AccountType::Result as u8,
@@ -80,113 +123,235 @@ impl ResultAccount {
             snippet,
             yes_mint,
             no_mint,
-            bump_seed,
+            collateral: 0,
+            payout_bps: 0,
+            payer: Pubkey::default(),
+            unpaired_yes: 0,
+            amm_liquidity: 0,
+            amm_yes: 0,
+            amm_no: 0,
+        }
+    }
+
+    /// Lamports the result's market maker quotes a yes token at, by default the signal results are
+    /// ranked by. `None` when it has no market maker.
+    pub fn amm_yes_price(&self) -> Option<u64> {
+        if self.amm_liquidity == 0 {
+            return None;
         }
+        lmsr::yes_price(self.amm_liquidity, self.amm_yes, self.amm_no)
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_result(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     url: String,
     name: String,
     snippet: String,
-    bump_seed: u8,
+    result_bump_seed: u8,
+    yes_mint_bump_seed: u8,
+    no_mint_bump_seed: u8,
+    book_bump_seed: u8,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let result_account_info = next_account_info(account_info_iter)?;
     let market_account_info = next_account_info(account_info_iter)?;
     let yes_mint_account_info = next_account_info(account_info_iter)?;
     let no_mint_account_info = next_account_info(account_info_iter)?;
-    let mint_authority_info = next_account_info(account_info_iter)?;
+    let book_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let payer_account_info = next_account_info(account_info_iter)?;
     let rent_account_info = next_account_info(account_info_iter)?;
     let spl_token_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
     let clock = Clock::get()?;
 
-    if !result_account_info.data.borrow().iter().all(|&b| b == 0) {
-        return Err(ProgramError::AccountAlreadyInitialized);
+    let config = ConfigAccount::load_unpaused(program_id, config_account_info)?;
+    if url.len() > config.params.max_url_len as usize {
+        msg!("Url longer than {}", config.params.max_url_len);
+        return Err(SearchMarketError::StringTooLong.into());
     }
 
     if *market_account_info.owner != *program_id {
         msg!("Market not owned by program");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
     }
-    let market = SearchMarketAccount::try_from_slice(*market_account_info.data.borrow())?;
+    let mut market = SearchMarketAccount::try_from_slice(*market_account_info.data.borrow())?;
     if market.expires_slot < clock.slot {
         msg!(
             "Market already expired: {} < {}",
             market.expires_slot,
             clock.slot
         );
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::MarketExpired.into());
     }
 
     if url::Url::parse(&url).is_err() {
         msg!("Url did not parse");
-        return Err(ProgramError::InvalidArgument);
+        return Err(SearchMarketError::InvalidUrl.into());
     }
 
-    if Pubkey::create_program_address(&[b"mint_authority", &[bump_seed]], program_id)?
-        != *mint_authority_info.key
+    if Pubkey::create_program_address(
+        &[
+            b"vault",
+            &market_account_info.key.to_bytes(),
+            &[market.vault_bump_seed],
+        ],
+        program_id,
+    )? != *vault_account_info.key
     {
-        msg!("Mint authority invalid");
-        return Err(ProgramError::InvalidArgument);
+        msg!("Vault invalid");
+        return Err(SearchMarketError::InvalidVault.into());
+    }
+
+    if !payer_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
     if *rent_account_info.key != rent::id() {
         msg!("Rent account invalid");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::InvalidRentSysvar.into());
     }
 
     if *spl_token_account_info.key != spl_token::id() {
         msg!("Token program account invalid");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::InvalidTokenProgram.into());
     }
 
-    invoke(
-        &spl_token::instruction::initialize_mint(
-            &spl_token::id(),
-            yes_mint_account_info.key,
-            mint_authority_info.key,
-            None,
-            spl_token::native_mint::DECIMALS,
-        )?,
-        &[
-            yes_mint_account_info.clone(),
-            rent_account_info.clone(),
-            spl_token_account_info.clone(),
-        ],
-    )?;
+    if *system_program_info.key != system_program::id() {
+        msg!("System program account invalid");
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let result_seeds: &[&[u8]] = &[
+        b"result",
+        &market_account_info.key.to_bytes(),
+        &market.result_count.to_le_bytes(),
+        &[result_bump_seed],
+    ];
+    if Pubkey::create_program_address(result_seeds, program_id)? != *result_account_info.key {
+        msg!("Result address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    let yes_mint_seeds: &[&[u8]] = &[
+        b"yes_mint",
+        &result_account_info.key.to_bytes(),
+        &[yes_mint_bump_seed],
+    ];
+    if Pubkey::create_program_address(yes_mint_seeds, program_id)? != *yes_mint_account_info.key {
+        msg!("Yes mint address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    let no_mint_seeds: &[&[u8]] = &[
+        b"no_mint",
+        &result_account_info.key.to_bytes(),
+        &[no_mint_bump_seed],
+    ];
+    if Pubkey::create_program_address(no_mint_seeds, program_id)? != *no_mint_account_info.key {
+        msg!("No mint address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    let book_seeds: &[&[u8]] = &[
+        b"book",
+        &result_account_info.key.to_bytes(),
+        &[book_bump_seed],
+    ];
+    if Pubkey::create_program_address(book_seeds, program_id)? != *book_account_info.key {
+        msg!("Book address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
 
-    invoke(
-        &spl_token::instruction::initialize_mint(
+    for (mint_account_info, mint_seeds) in [
+        (yes_mint_account_info, yes_mint_seeds),
+        (no_mint_account_info, no_mint_seeds),
+    ] {
+        create_pda_account(
+            payer_account_info,
+            mint_account_info,
+            system_program_info,
+            Mint::LEN,
             &spl_token::id(),
-            no_mint_account_info.key,
-            mint_authority_info.key,
-            None,
-            spl_token::native_mint::DECIMALS,
-        )?,
-        &[
-            no_mint_account_info.clone(),
-            rent_account_info.clone(),
-            spl_token_account_info.clone(),
-        ],
-    )?;
+            mint_seeds,
+        )?;
+
+        invoke(
+            &spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                mint_account_info.key,
+                vault_account_info.key,
+                None,
+                spl_token::native_mint::DECIMALS,
+            )?,
+            &[
+                mint_account_info.clone(),
+                rent_account_info.clone(),
+                spl_token_account_info.clone(),
+            ],
+        )?;
+    }
 
-    let result = ResultAccount::new(
+    let mut result = ResultAccount::new(
         *market_account_info.key,
         url,
         name,
         snippet,
         *yes_mint_account_info.key,
         *no_mint_account_info.key,
-        bump_seed,
     );
+    result.payer = *payer_account_info.key;
+    if market.amm_liquidity > 0 {
+        let subsidy =
+            lmsr::subsidy(market.amm_liquidity).ok_or(SearchMarketError::ArithmeticOverflow)?;
+        if market.amm_subsidy >= subsidy {
+            market.amm_subsidy -= subsidy;
+            result.amm_liquidity = market.amm_liquidity;
+            result.collateral = subsidy;
+        } else {
+            msg!("Market maker subsidy used up");
+        }
+    }
+    let data = result.try_to_vec()?;
+    create_pda_account(
+        payer_account_info,
+        result_account_info,
+        system_program_info,
+        data.len(),
+        program_id,
+        result_seeds,
+    )?;
+    result_account_info.data.borrow_mut().copy_from_slice(&data);
+
+    create_pda_account(
+        payer_account_info,
+        book_account_info,
+        system_program_info,
+        OrderBookAccount::space(),
+        program_id,
+        book_seeds,
+    )?;
+    OrderBookAccount::new(*result_account_info.key)
+        .serialize(&mut &mut book_account_info.data.borrow_mut()[..])?;
 
-    result
-        .serialize(&mut &mut result_account_info.data.borrow_mut()[..])
-        .map(|_| ())
-        .map_err(|e| e.into())
+    market.result_count = market
+        .result_count
+        .checked_add(1)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::ResultCreated {
+        market: *market_account_info.key,
+        result: *result_account_info.key,
+        url: result.url,
+        yes_mint: result.yes_mint,
+        no_mint: result.no_mint,
+    }
+    .emit()
 }
 
 #[cfg(test)]
@@ -196,53 +361,30 @@ pub mod test {
     use crate::instructions::test_utils::setup_market;
     use crate::process_instruction;
     use crate::test_utils::*;
-    use solana_program::program_pack::Pack;
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::{
         account::Account as SolanaAccount,
-        rent::Rent,
         signature::{Keypair, Signer},
         transaction::Transaction,
     };
-    use spl_token::state::Mint;
 
     pub fn setup_result(
         result: &mut ResultAccount,
-        program_test: &mut ProgramTest,
+        index: u64,
+        payer: &Pubkey,
         program_id: &Pubkey,
     ) -> (Pubkey, Instruction) {
-        let result_key = Pubkey::new_unique();
-        let (_mint_authority_key, bump_seed) =
-            Pubkey::find_program_address(&[b"mint_authority"], &program_id);
-        result.bump_seed = bump_seed;
-
-        let result_account = SolanaAccount::new(
-            minimum_balance(result).unwrap(),
-            space(result).unwrap(),
-            program_id,
-        );
-        program_test.add_account(result_key, result_account);
-
-        let yes_mint_account = SolanaAccount::new(
-            Rent::default().minimum_balance(Mint::LEN),
-            Mint::LEN,
-            &spl_token::id(),
-        );
-        program_test.add_account(result.yes_mint, yes_mint_account);
-
-        let no_mint_account = SolanaAccount::new(
-            Rent::default().minimum_balance(Mint::LEN),
-            Mint::LEN,
-            &spl_token::id(),
-        );
-        program_test.add_account(result.no_mint, no_mint_account);
+        let (result_key, _result_bump_seed) =
+            find_result_address(program_id, &result.search_market, index);
+        result.yes_mint = find_yes_mint_address(program_id, &result_key).0;
+        result.no_mint = find_no_mint_address(program_id, &result_key).0;
+        result.payer = *payer;
 
         let create_result_instruction = create_result_instruction(
             &program_id,
-            &result_key,
             &result.search_market,
-            &result.yes_mint,
-            &result.no_mint,
+            index,
+            payer,
             result.url.clone(),
             result.name.clone(),
             result.snippet.clone(),
@@ -259,9 +401,15 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 1);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            1,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -270,9 +418,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let mut transaction =
             Transaction::new_with_payer(&[create_market, create_result], Some(&payer.pubkey()));
@@ -298,5 +446,69 @@ pub mod test {
             .unwrap();
         let processed_mint = Mint::unpack_from_slice(&no_mint_account.data[..]).unwrap();
         assert_eq!(true, processed_mint.is_initialized);
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book, OrderBookAccount::new(result_key));
+    }
+
+    #[tokio::test]
+    async fn test_create_result_prefunded() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            1,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+
+        // Lamports sent ahead of time must not keep the addresses from being created.
+        for key in [result_key, result.yes_mint, book_key] {
+            program_test.add_account(key, SolanaAccount::new(1, 0, &system_program::id()));
+        }
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction =
+            Transaction::new_with_payer(&[create_market, create_result], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &decision_authority], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let result_account = banks_client.get_account(result_key).await.unwrap().unwrap();
+        assert_eq!(result_account.owner, program_id);
+        assert_eq!(result_account.lamports, minimum_balance(&result).unwrap());
+        let processed_result = ResultAccount::try_from_slice(&result_account.data[..]).unwrap();
+        assert_eq!(result, processed_result);
+
+        let yes_mint_account = banks_client
+            .get_account(result.yes_mint)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(yes_mint_account.owner, spl_token::id());
+        assert!(Mint::unpack(&yes_mint_account.data).unwrap().is_initialized);
+
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book, OrderBookAccount::new(result_key));
     }
 }
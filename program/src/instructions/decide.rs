@@ -1,10 +1,15 @@
-use super::{ResultAccount, SearchMarketAccount, SearchMarketInstruction};
+use super::{
+    fee_amount, transfer_program_lamports, MarketState, ResultAccount, SearchMarketAccount,
+    SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::{SearchMarketError, BASIS_POINTS};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    clock::Clock,
+    clock::{Clock, Slot},
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
+    msg,
     program_error::ProgramError,
     pubkey::Pubkey,
     sysvar::Sysvar,
@@ -15,13 +20,42 @@ pub fn decide_instruction(
     market_pubkey: &Pubkey,
     decision_authority_pubkey: &Pubkey,
     best_result_pubkey: &Pubkey,
+    fee_destination_pubkey: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    decide_multisig_instruction(
+        program_id,
+        market_pubkey,
+        decision_authority_pubkey,
+        &[*decision_authority_pubkey],
+        best_result_pubkey,
+        fee_destination_pubkey,
+    )
+}
+
+/// Decides with several signers of the market's signer set, or with its delegate. The market's
+/// `decision_authority` receives the bounty refund whoever signs.
+pub fn decide_multisig_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    decision_authority_pubkey: &Pubkey,
+    signers: &[Pubkey],
+    best_result_pubkey: &Pubkey,
+    fee_destination_pubkey: &Pubkey,
 ) -> Result<Instruction, std::io::Error> {
     let data = SearchMarketInstruction::Decide.try_to_vec()?;
-    let accounts = vec![
+    let (decider, co_signers) = signers.split_first().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no decision signers")
+    })?;
+    let mut accounts = vec![
         AccountMeta::new(*market_pubkey, false),
-        AccountMeta::new_readonly(*decision_authority_pubkey, true),
+        AccountMeta::new(*decider, true),
         AccountMeta::new_readonly(*best_result_pubkey, false),
+        AccountMeta::new(*fee_destination_pubkey, false),
+        AccountMeta::new(*decision_authority_pubkey, false),
     ];
+    for co_signer in co_signers {
+        accounts.push(AccountMeta::new_readonly(*co_signer, true));
+    }
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -29,56 +63,122 @@ pub fn decide_instruction(
     })
 }
 
+/// Records the best result once trading has closed, opening the challenge window. Any further
+/// signers of a multisig market follow the fixed accounts.
 pub fn decide(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let market_account_info = next_account_info(account_info_iter)?;
-    let decision_authority_info = next_account_info(account_info_iter)?;
+    let decider_info = next_account_info(account_info_iter)?;
     let best_result_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let decision_authority_info = next_account_info(account_info_iter)?;
     let clock = Clock::get()?;
 
     if *market_account_info.owner != *program_id {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
     }
-    let mut market =
-        SearchMarketAccount::try_from_slice(&market_account_info.data.borrow()).unwrap();
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
 
-    if !decision_authority_info.is_signer {
-        return Err(ProgramError::InvalidAccountData);
+    if !decider_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
+    let signers: Vec<Pubkey> = accounts
+        .iter()
+        .filter(|account| account.is_signer)
+        .map(|account| *account.key)
+        .collect();
 
     if *best_result_info.owner != *program_id {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
     }
     let result = ResultAccount::try_from_slice(&best_result_info.data.borrow())?;
     if result.search_market != *market_account_info.key {
-        return Err(ProgramError::InvalidAccountData);
-    }
-
-    if clock.slot > market.expires_slot {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::ResultNotInMarket.into());
     }
 
-    if market.decision_authority != *decision_authority_info.key {
-        return Err(ProgramError::InvalidAccountData);
-    }
     market.best_result = *best_result_info.key;
+    record_decision(
+        &mut market,
+        market_account_info,
+        decision_authority_info,
+        fee_destination_info,
+        &signers,
+        clock.slot,
+        vec![(*best_result_info.key, BASIS_POINTS as u16)],
+    )?;
 
     market
         .serialize(&mut &mut market_account_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
+/// Checks that `signers` may decide now and moves the market to `Decided`. This also releases the
+/// market's bounty, refunding `bounty_refund_bps` of it to the decision authority, even when a
+/// delegate or co-signers decide, and sending the rest to the fee destination. `payouts` lists the
+/// share of collateral each result pays for the event.
+pub(crate) fn record_decision(
+    market: &mut SearchMarketAccount,
+    market_account_info: &AccountInfo,
+    decision_authority_info: &AccountInfo,
+    fee_destination_info: &AccountInfo,
+    signers: &[Pubkey],
+    slot: Slot,
+    payouts: Vec<(Pubkey, u16)>,
+) -> ProgramResult {
+    if slot > market.decision_deadline {
+        return Err(SearchMarketError::MarketExpired.into());
+    }
+    let state = market.state(slot)?;
+    if state != MarketState::TradingClosed {
+        msg!("Cannot decide while {:?}", state);
+        return Err(SearchMarketError::InvalidMarketState.into());
+    }
+
+    if !market.can_decide(signers, slot) {
+        return Err(SearchMarketError::WrongDecisionAuthority.into());
+    }
+    if market.fee_destination != *fee_destination_info.key {
+        return Err(SearchMarketError::FeeDestinationMismatch.into());
+    }
+    if market.decision_authority != *decision_authority_info.key {
+        msg!("Bounty refund account is not the decision authority");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    market.state = MarketState::Decided as u8;
+    market.decided_slot = slot;
+
+    if market.bounty > 0 {
+        let refund = fee_amount(market.bounty, market.bounty_refund_bps)?;
+        transfer_program_lamports(market_account_info, decision_authority_info, refund)?;
+        transfer_program_lamports(
+            market_account_info,
+            fee_destination_info,
+            market.bounty - refund,
+        )?;
+        market.bounty = 0;
+    }
+
+    SearchMarketEvent::Decided {
+        market: *market_account_info.key,
+        best_result: market.best_result,
+        payouts,
+    }
+    .emit()
+}
+
 #[cfg(test)]
 #[cfg(feature = "test-bpf")]
 pub mod test {
     use super::*;
     use crate::instructions::test_utils::*;
     use crate::process_instruction;
+    use crate::test_utils::*;
     use crate::ResultAccount;
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::{
+        instruction::InstructionError,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
 
     #[tokio::test]
@@ -88,9 +188,18 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let mut market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.bounty = 1_000_000;
+        market.bounty_refund_bps = 2_500;
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -99,9 +208,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let some_other_authority = Keypair::new();
         let bad_decide_instruction = decide_instruction(
@@ -109,6 +218,7 @@ pub mod test {
             &market_key,
             &some_other_authority.pubkey(),
             &result_key,
+            &market.fee_destination,
         )
         .unwrap();
 
@@ -117,52 +227,146 @@ pub mod test {
             &market_key,
             &decision_authority.pubkey(),
             &result_key,
+            &market.fee_destination,
         )
         .unwrap();
 
-        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
-
+        let mut context = program_test.start_with_context().await;
         let mut transaction = Transaction::new_with_payer(
             &[
                 create_market.clone(),
                 create_result.clone(),
-                bad_decide_instruction,
+                good_decide_instruction.clone(),
             ],
-            Some(&payer.pubkey()),
+            Some(&context.payer.pubkey()),
         );
         transaction.sign(
-            &[&payer, &decision_authority, &some_other_authority],
-            recent_blockhash,
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
         );
-        banks_client
+        let error = context
+            .banks_client
             .process_transaction(transaction)
             .await
             .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(SearchMarketError::InvalidMarketState as u32)
+            )
+        );
 
         let mut transaction = Transaction::new_with_payer(
-            &[create_market, create_result, good_decide_instruction],
-            Some(&payer.pubkey()),
+            &[create_market, create_result],
+            Some(&context.payer.pubkey()),
+        );
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[bad_decide_instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, &some_other_authority], recent_blockhash);
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongDecisionAuthority as u32)
+            )
         );
-        transaction.sign(&[&payer, &decision_authority], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
+
+        let mut transaction = Transaction::new_with_payer(
+            &[good_decide_instruction.clone()],
+            Some(&context.payer.pubkey()),
+        );
+        transaction.sign(&[&context.payer, &decision_authority], recent_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
 
         market.best_result = result_key;
-        let market_account = banks_client.get_account(market_key).await.unwrap().unwrap();
+        market.result_count = 1;
+        market.bounty = 0;
+        market.state = MarketState::Decided as u8;
+        market.decided_slot = 3;
+        let market_account = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap();
         let processed_market =
             SearchMarketAccount::try_from_slice(&market_account.data[..]).unwrap();
         assert_eq!(market, processed_market);
+        assert_eq!(
+            market_account.lamports,
+            minimum_balance(&processed_market).unwrap()
+        );
+
+        let fee_destination = context
+            .banks_client
+            .get_account(market.fee_destination)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee_destination.lamports, 750_000);
+
+        let mut transaction = Transaction::new_with_payer(
+            &[good_decide_instruction],
+            Some(&decision_authority.pubkey()),
+        );
+        transaction.sign(&[&decision_authority], recent_blockhash);
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidMarketState as u32)
+            )
+        );
     }
 
     #[tokio::test]
-    async fn test_decide_too_late() {
+    async fn test_decide_multisig() {
         let program_id = crate::id();
         let mut program_test =
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let signer_keypairs = [Keypair::new(), Keypair::new(), Keypair::new()];
+        let signers: Vec<Pubkey> = signer_keypairs.iter().map(|k| k.pubkey()).collect();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.decision_signers = signers.clone();
+        market.decision_threshold = 2;
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -171,15 +375,120 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let duplicate_signers = decide_multisig_instruction(
+            &program_id,
+            &market_key,
+            &decision_authority.pubkey(),
+            &[signers[0], signers[0]],
+            &result_key,
+            &market.fee_destination,
+        )
+        .unwrap();
+        let threshold_signers = decide_multisig_instruction(
+            &program_id,
+            &market_key,
+            &decision_authority.pubkey(),
+            &[signers[2], signers[0]],
+            &result_key,
+            &market.fee_destination,
+        )
+        .unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[create_market, create_result],
+            Some(&context.payer.pubkey()),
+        );
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[duplicate_signers], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, &signer_keypairs[0]], recent_blockhash);
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongDecisionAuthority as u32)
+            )
+        );
+
+        let mut transaction =
+            Transaction::new_with_payer(&[threshold_signers], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &signer_keypairs[2], &signer_keypairs[0]],
+            recent_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let market_account = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let processed_market =
+            SearchMarketAccount::try_from_slice(&market_account.data[..]).unwrap();
+        assert_eq!(processed_market.best_result, result_key);
+    }
+
+    #[tokio::test]
+    async fn test_decide_too_late() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
             0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let decide_instruction = decide_instruction(
             &program_id,
             &market_key,
             &decision_authority.pubkey(),
             &result_key,
+            &market.fee_destination,
         )
         .unwrap();
 
@@ -211,6 +520,12 @@ pub mod test {
             .process_transaction(transaction)
             .await
             .unwrap_err();
-        assert_eq!(error.to_string(), "transport transaction error: Error processing Instruction 0: invalid account data for instruction");
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::MarketExpired as u32)
+            )
+        );
     }
 }
@@ -0,0 +1,120 @@
+use super::{record_decision, ResultAccount, SearchMarketAccount, SearchMarketInstruction};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+/// `ranking` lists results best first. The market's decision authority receives the bounty refund.
+pub fn decide_ranked_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    decision_authority_pubkey: &Pubkey,
+    signers: &[Pubkey],
+    fee_destination_pubkey: &Pubkey,
+    ranking: &[Pubkey],
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::DecideRanked {
+        count: ranking.len() as u8,
+    }
+    .try_to_vec()?;
+    let (decider, co_signers) = signers.split_first().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no decision signers")
+    })?;
+    let mut accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(*decider, true),
+        AccountMeta::new(*fee_destination_pubkey, false),
+        AccountMeta::new(*decision_authority_pubkey, false),
+    ];
+    for result in ranking {
+        accounts.push(AccountMeta::new_readonly(*result, false));
+    }
+    for co_signer in co_signers {
+        accounts.push(AccountMeta::new_readonly(*co_signer, true));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Decides with an ordered ranking of up to the schedule's length of results. The result at each
+/// rank pays its yes tokens the market's `rank_schedule` entry for that rank; unranked results
+/// resolve to no. The top result is recorded as `best_result`.
+pub fn decide_ranked(program_id: &Pubkey, accounts: &[AccountInfo], count: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let decider_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let decision_authority_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+
+    if !decider_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let signers: Vec<Pubkey> = accounts
+        .iter()
+        .filter(|account| account.is_signer)
+        .map(|account| *account.key)
+        .collect();
+
+    if count == 0 || count as usize > market.rank_schedule.len() {
+        msg!(
+            "Ranking of {} for a schedule of {}",
+            count,
+            market.rank_schedule.len()
+        );
+        return Err(SearchMarketError::InvalidRanking.into());
+    }
+
+    for rank in 0..count as usize {
+        let result_account_info = next_account_info(account_info_iter)?;
+        if *result_account_info.owner != *program_id {
+            return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+        }
+        let result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+        if result.search_market != *market_account_info.key {
+            return Err(SearchMarketError::ResultNotInMarket.into());
+        }
+        if market.ranking[..rank].contains(result_account_info.key) {
+            return Err(SearchMarketError::InvalidRanking.into());
+        }
+        market.ranking[rank] = *result_account_info.key;
+    }
+
+    market.best_result = market.ranking[0];
+    let payouts = market
+        .ranking
+        .iter()
+        .copied()
+        .zip(market.rank_schedule.iter().copied())
+        .take(count as usize)
+        .collect();
+    record_decision(
+        &mut market,
+        market_account_info,
+        decision_authority_info,
+        fee_destination_info,
+        &signers,
+        clock.slot,
+        payouts,
+    )?;
+
+    market
+        .serialize(&mut &mut market_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
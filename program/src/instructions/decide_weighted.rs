@@ -0,0 +1,132 @@
+use super::{record_decision, ResultAccount, SearchMarketAccount, SearchMarketInstruction};
+use crate::{SearchMarketError, BASIS_POINTS};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub const MAX_WEIGHTED_RESULTS: usize = 10;
+
+/// `weights` pairs results with their payout in basis points. The market's decision authority
+/// receives the bounty refund.
+pub fn decide_weighted_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    decision_authority_pubkey: &Pubkey,
+    signers: &[Pubkey],
+    fee_destination_pubkey: &Pubkey,
+    weights: &[(Pubkey, u16)],
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::DecideWeighted {
+        weights: weights.iter().map(|(_, weight)| *weight).collect(),
+    }
+    .try_to_vec()?;
+    let (decider, co_signers) = signers.split_first().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no decision signers")
+    })?;
+    let mut accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(*decider, true),
+        AccountMeta::new(*fee_destination_pubkey, false),
+        AccountMeta::new(*decision_authority_pubkey, false),
+    ];
+    for (result, _weight) in weights {
+        accounts.push(AccountMeta::new(*result, false));
+    }
+    for co_signer in co_signers {
+        accounts.push(AccountMeta::new_readonly(*co_signer, true));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Decides by splitting each token's payout across several results. A listed result's yes tokens
+/// redeem for its weight and its no tokens for the remainder; unlisted results resolve to no.
+/// The heaviest result is recorded as `best_result`.
+pub fn decide_weighted(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    weights: Vec<u16>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let decider_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let decision_authority_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+
+    if !decider_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let signers: Vec<Pubkey> = accounts
+        .iter()
+        .filter(|account| account.is_signer)
+        .map(|account| *account.key)
+        .collect();
+
+    let total: u64 = weights.iter().map(|weight| *weight as u64).sum();
+    if weights.is_empty() || weights.len() > MAX_WEIGHTED_RESULTS || total != BASIS_POINTS {
+        msg!("{} weights summing to {}", weights.len(), total);
+        return Err(SearchMarketError::InvalidPayoutWeights.into());
+    }
+
+    let mut results: Vec<(&AccountInfo, ResultAccount)> = Vec::with_capacity(weights.len());
+    for weight in &weights {
+        let result_account_info = next_account_info(account_info_iter)?;
+        if *result_account_info.owner != *program_id {
+            return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+        }
+        let mut result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+        if result.search_market != *market_account_info.key {
+            return Err(SearchMarketError::ResultNotInMarket.into());
+        }
+        if results
+            .iter()
+            .any(|(info, _)| info.key == result_account_info.key)
+        {
+            return Err(SearchMarketError::InvalidPayoutWeights.into());
+        }
+        result.payout_bps = *weight;
+        results.push((result_account_info, result));
+    }
+
+    if let Some((heaviest, _)) = results.iter().max_by_key(|(_, result)| result.payout_bps) {
+        market.best_result = *heaviest.key;
+    }
+    market.weighted = true;
+    let payouts = results
+        .iter()
+        .map(|(info, result)| (*info.key, result.payout_bps))
+        .collect();
+    record_decision(
+        &mut market,
+        market_account_info,
+        decision_authority_info,
+        fee_destination_info,
+        &signers,
+        clock.slot,
+        payouts,
+    )?;
+
+    for (result_account_info, result) in results {
+        result.serialize(&mut &mut result_account_info.data.borrow_mut()[..])?;
+    }
+    market
+        .serialize(&mut &mut market_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
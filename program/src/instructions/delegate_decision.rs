@@ -0,0 +1,266 @@
+use super::{SearchMarketAccount, SearchMarketEvent, SearchMarketInstruction};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Slot,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    pubkey::Pubkey,
+};
+
+pub fn delegate_decision_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    signers: &[Pubkey],
+    delegate: &Pubkey,
+    expires_slot: Slot,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::DelegateDecision {
+        delegate: *delegate,
+        expires_slot,
+    }
+    .try_to_vec()?;
+    let mut accounts = vec![AccountMeta::new(*market_pubkey, false)];
+    for signer in signers {
+        accounts.push(AccountMeta::new_readonly(*signer, true));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Lets `delegate` decide on the authority's behalf until `expires_slot`. Must be approved the same
+/// way a decision is, minus any current delegate; delegating to the default pubkey revokes.
+pub fn delegate_decision(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+    expires_slot: Slot,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+
+    let signers: Vec<Pubkey> = account_info_iter
+        .filter(|account| account.is_signer)
+        .map(|account| *account.key)
+        .collect();
+    if !market.approved_by(&signers) {
+        return Err(SearchMarketError::WrongDecisionAuthority.into());
+    }
+
+    msg!("Delegate {} until slot {}", delegate, expires_slot);
+    market.delegate = delegate;
+    market.delegate_expires_slot = expires_slot;
+
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::DecisionDelegated {
+        market: *market_account_info.key,
+        delegate,
+        expires_slot,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use crate::{decide_instruction, decide_multisig_instruction, ResultAccount};
+    use solana_program_test::{processor, ProgramTest};
+    use solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+
+    #[tokio::test]
+    async fn test_delegate_decision() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let signer_keypairs = [Keypair::new(), Keypair::new(), Keypair::new()];
+        let signers: Vec<Pubkey> = signer_keypairs.iter().map(|k| k.pubkey()).collect();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.decision_signers = signers.clone();
+        market.decision_threshold = 2;
+        market.bounty = 1_000_000;
+        market.bounty_refund_bps = 2_500;
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let delegate = Keypair::new();
+        let lone_delegation = delegate_decision_instruction(
+            &program_id,
+            &market_key,
+            &signers[..1],
+            &delegate.pubkey(),
+            10,
+        )
+        .unwrap();
+        let delegation = delegate_decision_instruction(
+            &program_id,
+            &market_key,
+            &signers[1..],
+            &delegate.pubkey(),
+            10,
+        )
+        .unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[create_market, create_result],
+            Some(&context.payer.pubkey()),
+        );
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[lone_delegation], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &signer_keypairs[0]],
+            context.last_blockhash,
+        );
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongDecisionAuthority as u32)
+            )
+        );
+
+        let mut transaction =
+            Transaction::new_with_payer(&[delegation], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &signer_keypairs[1], &signer_keypairs[2]],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+        let authority_decide = decide_instruction(
+            &program_id,
+            &market_key,
+            &decision_authority.pubkey(),
+            &result_key,
+            &market.fee_destination,
+        )
+        .unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[authority_decide], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, &decision_authority], recent_blockhash);
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongDecisionAuthority as u32)
+            )
+        );
+
+        let authority_before = context
+            .banks_client
+            .get_account(decision_authority.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        let delegate_decide = decide_multisig_instruction(
+            &program_id,
+            &market_key,
+            &decision_authority.pubkey(),
+            &[delegate.pubkey()],
+            &result_key,
+            &market.fee_destination,
+        )
+        .unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[delegate_decide], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, &delegate], recent_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let authority_after = context
+            .banks_client
+            .get_account(decision_authority.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        assert_eq!(authority_after, authority_before + 250_000);
+        let fee_destination = context
+            .banks_client
+            .get_account(market.fee_destination)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee_destination.lamports, 750_000);
+
+        let market_account = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let processed_market =
+            SearchMarketAccount::try_from_slice(&market_account.data[..]).unwrap();
+        assert_eq!(processed_market.best_result, result_key);
+        assert_eq!(processed_market.delegate, delegate.pubkey());
+    }
+}
@@ -1,5 +1,8 @@
-use super::{ResultAccount, SearchMarketAccount, SearchMarketInstruction};
-use crate::LAMPORTS_PER_TOKEN;
+use super::{
+    find_config_address, find_vault_address, ConfigAccount, OrderSide, ResultAccount,
+    SearchMarketAccount, SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::{SearchMarketError, LAMPORTS_PER_TOKEN};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
@@ -7,7 +10,7 @@ use solana_program::{
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
     system_instruction::transfer,
@@ -15,6 +18,7 @@ use solana_program::{
     sysvar::Sysvar,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn deposit_instruction(
     program_id: &Pubkey,
     market_pubkey: &Pubkey,
@@ -26,20 +30,20 @@ pub fn deposit_instruction(
     no_token_pubkey: &Pubkey,
     amount: u64,
 ) -> Result<Instruction, std::io::Error> {
-    let (mint_authority_key, _bump_seed) =
-        Pubkey::find_program_address(&[b"mint_authority"], program_id);
+    let (vault_key, _bump_seed) = find_vault_address(program_id, market_pubkey);
     let data = SearchMarketInstruction::Deposit { amount }.try_to_vec()?;
     let accounts = vec![
         AccountMeta::new_readonly(*market_pubkey, false),
-        AccountMeta::new_readonly(*result_pubkey, false),
+        AccountMeta::new(*result_pubkey, false),
         AccountMeta::new(*deposit_pubkey, true),
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
-        AccountMeta::new(mint_authority_key, false),
+        AccountMeta::new(vault_key, false),
         AccountMeta::new(*yes_mint_pubkey, false),
         AccountMeta::new(*yes_token_pubkey, false),
         AccountMeta::new(*no_mint_pubkey, false),
         AccountMeta::new(*no_token_pubkey, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
     ];
 
     Ok(Instruction {
@@ -52,121 +56,256 @@ pub fn deposit_instruction(
 pub fn deposit(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let market_account_info = next_account_info(account_info_iter)?;
-    msg!("Market {}", market_account_info.key.to_string());
     let result_account_info = next_account_info(account_info_iter)?;
-    msg!("result {}", result_account_info.key.to_string());
     let deposit_account_info = next_account_info(account_info_iter)?;
-    msg!("deposit {}", deposit_account_info.key.to_string());
     let system_program_info = next_account_info(account_info_iter)?;
-    msg!("system {}", system_program_info.key.to_string());
     let spl_token_program_info = next_account_info(account_info_iter)?;
-    msg!("spl {}", spl_token_program_info.key.to_string());
-    let mint_authority_info = next_account_info(account_info_iter)?;
-    msg!("mint auth {}", mint_authority_info.key.to_string());
+    let vault_account_info = next_account_info(account_info_iter)?;
     let yes_mint_account_info = next_account_info(account_info_iter)?;
-    msg!("yes mint {}", yes_mint_account_info.key.to_string());
     let yes_token_account_info = next_account_info(account_info_iter)?;
-    msg!("yes token {}", yes_token_account_info.key.to_string());
     let no_mint_account_info = next_account_info(account_info_iter)?;
-    msg!("no mint {}", no_mint_account_info.key.to_string());
     let no_token_account_info = next_account_info(account_info_iter)?;
-    msg!("no token {}", no_token_account_info.key.to_string());
+    let config_account_info = next_account_info(account_info_iter)?;
     let clock = Clock::get()?;
 
+    ConfigAccount::load_unpaused(program_id, config_account_info)?;
+
     if *market_account_info.owner != *program_id {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
     }
     let market = SearchMarketAccount::try_from_slice(*market_account_info.data.borrow())?;
     if clock.slot > market.expires_slot {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::MarketExpired.into());
     }
 
     if *result_account_info.owner != *program_id {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
     }
-    let result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    let mut result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
     if result.search_market != *market_account_info.key {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::ResultNotInMarket.into());
     }
 
     if *system_program_info.key != system_program::id() {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::InvalidSystemProgram.into());
     }
 
     if *spl_token_program_info.key != spl_token::id() {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::InvalidTokenProgram.into());
     }
 
-    if Pubkey::create_program_address(&[b"mint_authority", &[result.bump_seed]], program_id)?
-        != *mint_authority_info.key
-    {
-        return Err(ProgramError::InvalidArgument);
+    let vault_seeds: &[&[u8]] = &[
+        b"vault",
+        &market_account_info.key.to_bytes(),
+        &[market.vault_bump_seed],
+    ];
+    if Pubkey::create_program_address(vault_seeds, program_id)? != *vault_account_info.key {
+        return Err(SearchMarketError::InvalidVault.into());
     }
 
     if *yes_mint_account_info.key != result.yes_mint {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::MintMismatch.into());
     }
 
     if *no_mint_account_info.key != result.no_mint {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::MintMismatch.into());
     }
 
+    let deposit_amount = amount
+        .checked_mul(LAMPORTS_PER_TOKEN)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
     msg!("transfer sol");
-    invoke_signed(
+    invoke(
         &transfer(
             deposit_account_info.key,
-            mint_authority_info.key,
-            amount * LAMPORTS_PER_TOKEN,
+            vault_account_info.key,
+            deposit_amount,
         ),
         &[
             deposit_account_info.clone(),
-            mint_authority_info.clone(),
+            vault_account_info.clone(),
             system_program_info.clone(),
         ],
-        &[&[b"mint_authority", &[result.bump_seed]]],
     )?;
 
-    msg!("mint yes");
-    invoke_signed(
-        &spl_token::instruction::mint_to(
-            spl_token_program_info.key,
-            yes_mint_account_info.key,
-            yes_token_account_info.key,
-            mint_authority_info.key,
-            &[],
-            amount,
-        )?,
-        &[
-            yes_mint_account_info.clone(),
-            yes_token_account_info.clone(),
-            mint_authority_info.clone(),
-            spl_token_program_info.clone(),
-        ],
-        &[&[b"mint_authority", &[result.bump_seed]]],
+    mint_pair(
+        vault_account_info,
+        vault_seeds,
+        yes_mint_account_info,
+        yes_token_account_info,
+        no_mint_account_info,
+        no_token_account_info,
+        spl_token_program_info,
+        amount,
     )?;
 
-    msg!("mint no");
-    invoke_signed(
-        &spl_token::instruction::mint_to(
-            spl_token_program_info.key,
-            no_mint_account_info.key,
-            no_token_account_info.key,
-            mint_authority_info.key,
-            &[],
-            amount,
-        )?,
-        &[
-            no_mint_account_info.clone(),
-            no_token_account_info.clone(),
-            mint_authority_info.clone(),
-            spl_token_program_info.clone(),
-        ],
-        &[&[b"mint_authority", &[result.bump_seed]]],
+    result.collateral = result
+        .collateral
+        .checked_add(deposit_amount)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    result.serialize(&mut &mut result_account_info.data.borrow_mut()[..])?;
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_result_backing(
+        &market,
+        result_account_info.key,
+        &result,
+        vault_account_info,
+        yes_mint_account_info,
+        no_mint_account_info,
     )?;
 
+    SearchMarketEvent::Deposited {
+        market: *market_account_info.key,
+        result: *result_account_info.key,
+        owner: *deposit_account_info.key,
+        amount,
+    }
+    .emit()
+}
+
+/// Mints `amount` yes and no tokens against collateral already paid into the vault, which is the
+/// mint authority of both.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn mint_pair<'a>(
+    vault: &AccountInfo<'a>,
+    vault_seeds: &[&[u8]],
+    yes_mint: &AccountInfo<'a>,
+    yes_token: &AccountInfo<'a>,
+    no_mint: &AccountInfo<'a>,
+    no_token: &AccountInfo<'a>,
+    spl_token_program: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    for (mint, token) in [(yes_mint, yes_token), (no_mint, no_token)] {
+        msg!("mint {}", mint.key);
+        invoke_signed(
+            &spl_token::instruction::mint_to(
+                spl_token_program.key,
+                mint.key,
+                token.key,
+                vault.key,
+                &[],
+                amount,
+            )?,
+            &[
+                mint.clone(),
+                token.clone(),
+                vault.clone(),
+                spl_token_program.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+    }
     Ok(())
 }
 
+/// The vault and mints a result's complementary buys pay into and mint from while matching.
+pub(crate) struct PairMint<'a, 'b> {
+    market_key: &'a Pubkey,
+    market: &'a SearchMarketAccount,
+    pub vault: &'a AccountInfo<'b>,
+    yes_mint: &'a AccountInfo<'b>,
+    no_mint: &'a AccountInfo<'b>,
+    /// Pairs minted so far, which the caller adds to the result's collateral.
+    pub minted: u64,
+}
+
+impl<'a, 'b> PairMint<'a, 'b> {
+    pub(crate) fn new(
+        program_id: &Pubkey,
+        market_key: &'a Pubkey,
+        market: &'a SearchMarketAccount,
+        result: &ResultAccount,
+        vault: &'a AccountInfo<'b>,
+        yes_mint: &'a AccountInfo<'b>,
+        no_mint: &'a AccountInfo<'b>,
+    ) -> Result<Self, ProgramError> {
+        if Pubkey::create_program_address(
+            &[b"vault", &market_key.to_bytes(), &[market.vault_bump_seed]],
+            program_id,
+        )? != *vault.key
+        {
+            return Err(SearchMarketError::InvalidVault.into());
+        }
+        if *yes_mint.key != result.yes_mint || *no_mint.key != result.no_mint {
+            return Err(SearchMarketError::MintMismatch.into());
+        }
+        Ok(PairMint {
+            market_key,
+            market,
+            vault,
+            yes_mint,
+            no_mint,
+            minted: 0,
+        })
+    }
+
+    pub(crate) fn mint(
+        &mut self,
+        yes_token: &AccountInfo<'b>,
+        no_token: &AccountInfo<'b>,
+        spl_token_program: &AccountInfo<'b>,
+        amount: u64,
+    ) -> ProgramResult {
+        mint_pair(
+            self.vault,
+            &[
+                b"vault",
+                &self.market_key.to_bytes(),
+                &[self.market.vault_bump_seed],
+            ],
+            self.yes_mint,
+            yes_token,
+            self.no_mint,
+            no_token,
+            spl_token_program,
+            amount,
+        )?;
+        self.minted = self
+            .minted
+            .checked_add(amount)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// The mint a buy on `side` is paid in.
+    pub(crate) fn buy_mint(&self, side: OrderSide) -> &Pubkey {
+        match side {
+            OrderSide::BuyNo => self.no_mint.key,
+            OrderSide::Buy | OrderSide::Sell => self.yes_mint.key,
+        }
+    }
+
+    /// Adds the collateral paid in for the pairs minted so far to `result` and writes it back.
+    pub(crate) fn record_collateral(
+        &self,
+        result: &mut ResultAccount,
+        result_account_info: &AccountInfo,
+    ) -> ProgramResult {
+        if self.minted == 0 {
+            return Ok(());
+        }
+        result.collateral = self
+            .minted
+            .checked_mul(LAMPORTS_PER_TOKEN)
+            .and_then(|paid| result.collateral.checked_add(paid))
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        result.serialize(&mut &mut result_account_info.data.borrow_mut()[..])?;
+
+        #[cfg(feature = "debug-invariants")]
+        super::check_result_backing(
+            self.market,
+            result_account_info.key,
+            result,
+            self.vault,
+            self.yes_mint,
+            self.no_mint,
+        )?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "test-bpf")]
 pub mod test {
@@ -179,9 +318,10 @@ pub mod test {
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::{
         account::Account as SolanaAccount,
+        instruction::InstructionError,
         rent::Rent,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
     use spl_token::state::Account;
 
@@ -250,9 +390,15 @@ pub mod test {
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 1);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            1,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -261,9 +407,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
         let result_min_balance = minimum_balance(&result).unwrap();
 
         let deposit_keypair = Keypair::new();
@@ -312,16 +458,19 @@ pub mod test {
 
         let result_account = banks_client.get_account(result_key).await.unwrap().unwrap();
         assert_eq!(result_min_balance, result_account.lamports);
+        let processed_result = ResultAccount::try_from_slice(&result_account.data).unwrap();
+        assert_eq!(processed_result.collateral, 100 * LAMPORTS_PER_TOKEN);
 
-        let mint_authority_key =
-            Pubkey::create_program_address(&[b"mint_authority", &[result.bump_seed]], &program_id)
-                .unwrap();
-        let mint_authority_account = banks_client
-            .get_account(mint_authority_key)
-            .await
-            .unwrap()
-            .unwrap();
-        assert_eq!(mint_authority_account.lamports, 100 * LAMPORTS_PER_TOKEN);
+        let vault_key = Pubkey::create_program_address(
+            &[b"vault", &market_key.to_bytes(), &[market.vault_bump_seed]],
+            &program_id,
+        )
+        .unwrap();
+        let vault_account = banks_client.get_account(vault_key).await.unwrap().unwrap();
+        assert_eq!(
+            vault_account.lamports,
+            Rent::default().minimum_balance(0) + 100 * LAMPORTS_PER_TOKEN
+        );
 
         let deposit_account = banks_client
             .get_account(deposit_keypair.pubkey())
@@ -346,4 +495,180 @@ pub mod test {
         let no_token_data = Account::unpack_from_slice(&no_token_account.data).unwrap();
         assert_eq!(no_token_data.amount, 100);
     }
+
+    #[tokio::test]
+    async fn test_deposit_other_market_vault() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            1,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+        let other_authority = Keypair::new();
+        let mut other_market =
+            SearchMarketAccount::new(other_authority.pubkey(), "witcher".to_string(), 1, 10, 0);
+        let (other_market_key, create_other_market) =
+            setup_market(&mut other_market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let mut deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+        let (other_vault_key, _bump_seed) = find_vault_address(&program_id, &other_market_key);
+        deposit_instruction.accounts[5].pubkey = other_vault_key;
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_other_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                deposit_instruction,
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &decision_authority,
+                &other_authority,
+                &deposit_keypair,
+            ],
+            recent_blockhash,
+        );
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                5,
+                InstructionError::Custom(SearchMarketError::InvalidVault as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deposit_overflow() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            1,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let deposit_keypair = Keypair::new();
+        let (yes_token_pubkey, init_yes_token) = setup_token(
+            &result.yes_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+        let (no_token_pubkey, init_no_token) = setup_token(
+            &result.no_mint,
+            &deposit_keypair.pubkey(),
+            &mut program_test,
+        );
+
+        let mut deposit_instruction = setup_deposit(
+            &deposit_keypair.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token_pubkey,
+            &no_token_pubkey,
+            &mut program_test,
+            &program_id,
+        );
+        deposit_instruction.data = SearchMarketInstruction::Deposit { amount: u64::MAX }
+            .try_to_vec()
+            .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                create_market,
+                create_result,
+                init_yes_token,
+                init_no_token,
+                deposit_instruction,
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[&payer, &decision_authority, &deposit_keypair],
+            recent_blockhash,
+        );
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                4,
+                InstructionError::Custom(SearchMarketError::ArithmeticOverflow as u32)
+            )
+        );
+    }
 }
@@ -0,0 +1,618 @@
+use super::{
+    find_config_address, transfer_program_lamports, ConfigAccount, MarketState, ResultAccount,
+    SearchMarketAccount, SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+
+fn load_market(
+    program_id: &Pubkey,
+    market_account_info: &AccountInfo,
+) -> Result<SearchMarketAccount, ProgramError> {
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    SearchMarketAccount::try_from_slice(&market_account_info.data.borrow()).map_err(|e| e.into())
+}
+
+pub fn challenge_decision_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    challenger_pubkey: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::ChallengeDecision.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(*challenger_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Escalates a decision to the arbiter. The challenger's bond is held on the market account until
+/// the arbiter rules, or until `finalize_market` once the arbiter's window has passed.
+pub fn challenge_decision(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let challenger_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    let mut market = load_market(program_id, market_account_info)?;
+
+    if !challenger_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    if market.state(clock.slot)? != MarketState::Decided {
+        return Err(SearchMarketError::InvalidMarketState.into());
+    }
+    if market.challenger != Pubkey::default() {
+        return Err(SearchMarketError::AlreadyChallenged.into());
+    }
+    if clock.slot > market.decided_slot.saturating_add(market.challenge_window) {
+        return Err(SearchMarketError::ChallengeWindowClosed.into());
+    }
+
+    msg!("Challenge bond {}", market.challenge_bond);
+    invoke(
+        &system_instruction::transfer(
+            challenger_account_info.key,
+            market_account_info.key,
+            market.challenge_bond,
+        ),
+        &[
+            challenger_account_info.clone(),
+            market_account_info.clone(),
+            system_program_info.clone(),
+        ],
+    )?;
+    market.challenger = *challenger_account_info.key;
+    market.challenged_slot = clock.slot;
+
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::Challenged {
+        market: *market_account_info.key,
+        challenger: market.challenger,
+        bond: market.challenge_bond,
+    }
+    .emit()
+}
+
+pub fn resolve_challenge_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    arbiter_pubkey: &Pubkey,
+    best_result_pubkey: &Pubkey,
+    challenger_pubkey: &Pubkey,
+    fee_destination_pubkey: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::ResolveChallenge.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new_readonly(*arbiter_pubkey, true),
+        AccountMeta::new_readonly(*best_result_pubkey, false),
+        AccountMeta::new(*challenger_pubkey, false),
+        AccountMeta::new(*fee_destination_pubkey, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// The arbiter's ruling on a challenged decision is final. If it overturns the decision the market
+/// resolves to the arbiter's single best result and the bond is returned to the challenger,
+/// otherwise the bond goes to the market's fee destination. The arbiter has `arbiter_window` slots
+/// from the challenge to rule.
+pub fn resolve_challenge(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let market_account_info = next_account_info(account_info_iter)?;
+    let arbiter_account_info = next_account_info(account_info_iter)?;
+    let best_result_info = next_account_info(account_info_iter)?;
+    let challenger_account_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    let config = ConfigAccount::load(program_id, config_account_info)?;
+    let mut market = load_market(program_id, market_account_info)?;
+
+    if !arbiter_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.params.arbiter != *arbiter_account_info.key {
+        return Err(SearchMarketError::WrongArbiter.into());
+    }
+
+    if market.state != MarketState::Decided as u8 || market.challenger == Pubkey::default() {
+        return Err(SearchMarketError::InvalidMarketState.into());
+    }
+    if market.challenger != *challenger_account_info.key {
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    if clock.slot > market.arbiter_deadline() {
+        return Err(SearchMarketError::ArbiterWindowClosed.into());
+    }
+    if market.fee_destination != *fee_destination_info.key {
+        return Err(SearchMarketError::FeeDestinationMismatch.into());
+    }
+
+    if *best_result_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let result = ResultAccount::try_from_slice(&best_result_info.data.borrow())?;
+    if result.search_market != *market_account_info.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+
+    let overturned = market.best_result != *best_result_info.key;
+    let bond_destination = if overturned {
+        msg!("Decision overturned");
+        market.weighted = false;
+        market.ranking.fill(Pubkey::default());
+        challenger_account_info
+    } else {
+        msg!("Decision upheld");
+        fee_destination_info
+    };
+    transfer_program_lamports(market_account_info, bond_destination, market.challenge_bond)?;
+
+    market.best_result = *best_result_info.key;
+    market.state = MarketState::Finalized as u8;
+    market.settled_slot = clock.slot;
+
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::ChallengeResolved {
+        market: *market_account_info.key,
+        best_result: market.best_result,
+        overturned,
+    }
+    .emit()
+}
+
+/// `challenger_pubkey` is only needed for a challenged decision, to return the bond to.
+pub fn finalize_market_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    challenger_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::FinalizeMarket.try_to_vec()?;
+    let mut accounts = vec![AccountMeta::new(*market_pubkey, false)];
+    if let Some(challenger_pubkey) = challenger_pubkey {
+        accounts.push(AccountMeta::new(*challenger_pubkey, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Anyone may finalize a decision that went unchallenged through its window, or a market whose
+/// decision deadline passed without a decision, in which case no result wins. A challenged
+/// decision the arbiter did not rule on in time stands, and the challenger gets the bond back.
+pub fn finalize_market(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    let mut market = load_market(program_id, market_account_info)?;
+
+    match market.state(clock.slot)? {
+        MarketState::Decided => {
+            if market.challenger != Pubkey::default() {
+                if clock.slot <= market.arbiter_deadline() {
+                    msg!("Decision awaits the arbiter");
+                    return Err(SearchMarketError::InvalidMarketState.into());
+                }
+                let challenger_account_info = next_account_info(account_info_iter)?;
+                if market.challenger != *challenger_account_info.key {
+                    return Err(SearchMarketError::InvalidAccountAddress.into());
+                }
+                msg!("Arbiter did not rule, decision stands");
+                transfer_program_lamports(
+                    market_account_info,
+                    challenger_account_info,
+                    market.challenge_bond,
+                )?;
+            } else if clock.slot <= market.decided_slot.saturating_add(market.challenge_window) {
+                return Err(SearchMarketError::ChallengeWindowOpen.into());
+            }
+        }
+        MarketState::TradingClosed if clock.slot > market.decision_deadline => {
+            msg!("Finalizing without a decision");
+        }
+        state => {
+            msg!("Cannot finalize while {:?}", state);
+            return Err(SearchMarketError::InvalidMarketState.into());
+        }
+    }
+    market.state = MarketState::Finalized as u8;
+    market.settled_slot = clock.slot;
+
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::Finalized {
+        market: *market_account_info.key,
+        best_result: market.best_result,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::decide_instruction;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+
+    struct DisputeTest {
+        program_id: Pubkey,
+        decision_authority: Keypair,
+        arbiter: Keypair,
+        challenger: Keypair,
+        market_key: Pubkey,
+        market: SearchMarketAccount,
+        decided_key: Pubkey,
+        other_key: Pubkey,
+    }
+
+    /// A market with two results in which the first was decided at slot 3.
+    async fn setup_decided_market() -> (DisputeTest, ProgramTestContext) {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let arbiter = Keypair::new();
+        let mut params = test_config_params();
+        params.arbiter = arbiter.pubkey();
+        setup_config_with_params(
+            &mut program_test,
+            &program_id,
+            &Pubkey::new_unique(),
+            params,
+        );
+
+        let challenger = Keypair::new();
+        program_test.add_account(
+            challenger.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+
+        let mut decided = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (decided_key, create_decided) =
+            setup_result(&mut decided, 0, &decision_authority.pubkey(), &program_id);
+        let mut other = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk2077.com"),
+            String::from("Cyberpunk 2077"),
+            String::from("Official site"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (other_key, create_other) =
+            setup_result(&mut other, 1, &decision_authority.pubkey(), &program_id);
+
+        let decide = decide_instruction(
+            &program_id,
+            &market_key,
+            &decision_authority.pubkey(),
+            &decided_key,
+            &market.fee_destination,
+        )
+        .unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction = Transaction::new_with_payer(
+            &[create_market, create_decided, create_other],
+            Some(&context.payer.pubkey()),
+        );
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let dispute_test = DisputeTest {
+            program_id,
+            decision_authority,
+            arbiter,
+            challenger,
+            market_key,
+            market,
+            decided_key,
+            other_key,
+        };
+        dispute_test
+            .process(&mut context, decide, &[&dispute_test.decision_authority])
+            .await
+            .unwrap();
+        (dispute_test, context)
+    }
+
+    impl DisputeTest {
+        async fn process(
+            &self,
+            context: &mut ProgramTestContext,
+            instruction: Instruction,
+            signers: &[&Keypair],
+        ) -> Result<(), TransactionError> {
+            let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+            let mut all_signers = vec![&context.payer];
+            all_signers.extend_from_slice(signers);
+            transaction.sign(&all_signers, recent_blockhash);
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|e| e.unwrap())
+        }
+
+        async fn market(&self, context: &mut ProgramTestContext) -> SearchMarketAccount {
+            let market_account = context
+                .banks_client
+                .get_account(self.market_key)
+                .await
+                .unwrap()
+                .unwrap();
+            SearchMarketAccount::try_from_slice(&market_account.data).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_finalize_unchallenged() {
+        let (dispute_test, mut context) = setup_decided_market().await;
+        let finalize =
+            finalize_market_instruction(&dispute_test.program_id, &dispute_test.market_key, None)
+                .unwrap();
+
+        let error = dispute_test
+            .process(&mut context, finalize.clone(), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::ChallengeWindowOpen as u32)
+            )
+        );
+
+        context.warp_to_slot(6).unwrap();
+        dispute_test
+            .process(&mut context, finalize, &[])
+            .await
+            .unwrap();
+
+        let challenge = challenge_decision_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            &dispute_test.challenger.pubkey(),
+        )
+        .unwrap();
+        let error = dispute_test
+            .process(&mut context, challenge, &[&dispute_test.challenger])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidMarketState as u32)
+            )
+        );
+
+        let market = dispute_test.market(&mut context).await;
+        assert_eq!(market.state(6).unwrap(), MarketState::Finalized);
+        assert_eq!(market.best_result, dispute_test.decided_key);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_overturned() {
+        let (dispute_test, mut context) = setup_decided_market().await;
+
+        let challenge = challenge_decision_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            &dispute_test.challenger.pubkey(),
+        )
+        .unwrap();
+        dispute_test
+            .process(&mut context, challenge, &[&dispute_test.challenger])
+            .await
+            .unwrap();
+
+        let finalize =
+            finalize_market_instruction(&dispute_test.program_id, &dispute_test.market_key, None)
+                .unwrap();
+        context.warp_to_slot(6).unwrap();
+        let error = dispute_test
+            .process(&mut context, finalize, &[])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidMarketState as u32)
+            )
+        );
+
+        let wrong_arbiter = resolve_challenge_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            &dispute_test.decision_authority.pubkey(),
+            &dispute_test.other_key,
+            &dispute_test.challenger.pubkey(),
+            &dispute_test.market.fee_destination,
+        )
+        .unwrap();
+        let error = dispute_test
+            .process(
+                &mut context,
+                wrong_arbiter,
+                &[&dispute_test.decision_authority],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongArbiter as u32)
+            )
+        );
+
+        let resolve = resolve_challenge_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            &dispute_test.arbiter.pubkey(),
+            &dispute_test.other_key,
+            &dispute_test.challenger.pubkey(),
+            &dispute_test.market.fee_destination,
+        )
+        .unwrap();
+        dispute_test
+            .process(&mut context, resolve, &[&dispute_test.arbiter])
+            .await
+            .unwrap();
+
+        let market = dispute_test.market(&mut context).await;
+        assert_eq!(market.state(6).unwrap(), MarketState::Finalized);
+        assert_eq!(market.best_result, dispute_test.other_key);
+        assert_eq!(market.challenger, dispute_test.challenger.pubkey());
+
+        let challenger_account = context
+            .banks_client
+            .get_account(dispute_test.challenger.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(challenger_account.lamports, LAMPORTS_PER_SOL);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_unresolved() {
+        let (dispute_test, mut context) = setup_decided_market().await;
+
+        let challenge = challenge_decision_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            &dispute_test.challenger.pubkey(),
+        )
+        .unwrap();
+        dispute_test
+            .process(&mut context, challenge, &[&dispute_test.challenger])
+            .await
+            .unwrap();
+        let market = dispute_test.market(&mut context).await;
+
+        context.warp_to_slot(market.arbiter_deadline() + 1).unwrap();
+        let resolve = resolve_challenge_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            &dispute_test.arbiter.pubkey(),
+            &dispute_test.other_key,
+            &dispute_test.challenger.pubkey(),
+            &dispute_test.market.fee_destination,
+        )
+        .unwrap();
+        let error = dispute_test
+            .process(&mut context, resolve, &[&dispute_test.arbiter])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::ArbiterWindowClosed as u32)
+            )
+        );
+
+        let finalize = finalize_market_instruction(
+            &dispute_test.program_id,
+            &dispute_test.market_key,
+            Some(&dispute_test.challenger.pubkey()),
+        )
+        .unwrap();
+        dispute_test
+            .process(&mut context, finalize, &[])
+            .await
+            .unwrap();
+
+        let market = dispute_test.market(&mut context).await;
+        assert_eq!(
+            market.state(market.settled_slot).unwrap(),
+            MarketState::Finalized
+        );
+        assert_eq!(market.best_result, dispute_test.decided_key);
+
+        let challenger_account = context
+            .banks_client
+            .get_account(dispute_test.challenger.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(challenger_account.lamports, LAMPORTS_PER_SOL);
+    }
+}
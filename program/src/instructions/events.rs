@@ -0,0 +1,247 @@
+//! Events the program logs through `sol_log_data` so indexers can follow markets from transaction
+//! logs alone. Each event is one data field: the `EVENT_VERSION` byte followed by the Borsh
+//! encoding of a `SearchMarketEvent`. Variants are only ever appended, and a change to an
+//! existing variant's fields bumps the version.
+
+use super::ConfigParams;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{clock::Slot, entrypoint::ProgramResult, log::sol_log_data, pubkey::Pubkey};
+use std::io::{Error, ErrorKind};
+
+pub const EVENT_VERSION: u8 = 1;
+/// Prefix the runtime puts on each `sol_log_data` line of a transaction's logs.
+pub const EVENT_LOG_PREFIX: &str = "Program data: ";
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum SearchMarketEvent {
+    MarketCreated {
+        market: Pubkey,
+        creator: Pubkey,
+        decision_authority: Pubkey,
+        search_string: String,
+        expires_slot: Slot,
+        tick_size: u64,
+    },
+    ResultCreated {
+        market: Pubkey,
+        result: Pubkey,
+        url: String,
+        yes_mint: Pubkey,
+        no_mint: Pubkey,
+    },
+    /// `amount` tokens of both yes and no minted against collateral.
+    Deposited {
+        market: Pubkey,
+        result: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    },
+    /// `amount` pairs burned for their collateral.
+    Withdrawn {
+        market: Pubkey,
+        result: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    },
+    /// An order left resting on the book with `quantity` still open.
+    OrderPlaced {
+        result: Pubkey,
+        order: Pubkey,
+        owner: Pubkey,
+        side: u8,
+        price: u64,
+        quantity: u64,
+    },
+    /// `quantity` of a resting order taken at its price by the owner of the incoming order; `side`
+    /// is the resting order's.
+    OrderFilled {
+        result: Pubkey,
+        maker_order: Pubkey,
+        taker: Pubkey,
+        side: u8,
+        price: u64,
+        quantity: u64,
+    },
+    OrderCancelled {
+        result: Pubkey,
+        order: Pubkey,
+    },
+    /// The decision and the share of a winning token's collateral each listed result pays out.
+    Decided {
+        market: Pubkey,
+        best_result: Pubkey,
+        payouts: Vec<(Pubkey, u16)>,
+    },
+    /// `amount` tokens of `mint` burned for `lamports` of collateral.
+    Redeemed {
+        market: Pubkey,
+        result: Pubkey,
+        owner: Pubkey,
+        mint: Pubkey,
+        amount: u64,
+        lamports: u64,
+    },
+    Voided {
+        market: Pubkey,
+        void_yes_bps: u16,
+    },
+    Challenged {
+        market: Pubkey,
+        challenger: Pubkey,
+        bond: u64,
+    },
+    /// The arbiter's ruling, which also finalizes the market.
+    ChallengeResolved {
+        market: Pubkey,
+        best_result: Pubkey,
+        overturned: bool,
+    },
+    /// Finalized by anyone once nothing can change the outcome; `best_result` is the default
+    /// pubkey when no decision was made.
+    Finalized {
+        market: Pubkey,
+        best_result: Pubkey,
+    },
+    /// `amount` no tokens of every other result burned for as many yes tokens of `result`.
+    ConvertedNoToYes {
+        market: Pubkey,
+        result: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    },
+    BoughtFromAmm {
+        result: Pubkey,
+        trader: Pubkey,
+        yes: bool,
+        amount: u64,
+        cost: u64,
+    },
+    SoldToAmm {
+        result: Pubkey,
+        trader: Pubkey,
+        yes: bool,
+        amount: u64,
+        proceeds: u64,
+    },
+    PoolCreated {
+        pool: Pubkey,
+        result: Pubkey,
+        fee_bps: u16,
+    },
+    LiquidityAdded {
+        pool: Pubkey,
+        provider: Pubkey,
+        yes_amount: u64,
+        sol_amount: u64,
+        lp_amount: u64,
+    },
+    LiquidityRemoved {
+        pool: Pubkey,
+        owner: Pubkey,
+        yes_amount: u64,
+        sol_amount: u64,
+        lp_amount: u64,
+    },
+    /// `amount_in` lamports swapped for yes tokens when `buy_yes`, yes tokens for lamports if not.
+    Swapped {
+        pool: Pubkey,
+        trader: Pubkey,
+        buy_yes: bool,
+        amount_in: u64,
+        amount_out: u64,
+    },
+    /// `swept` lamports of unredeemed collateral went to the fee destination.
+    ResultClosed {
+        market: Pubkey,
+        result: Pubkey,
+        swept: u64,
+    },
+    MarketClosed {
+        market: Pubkey,
+    },
+    /// An unearned bounty sent to the fee destination.
+    BountyExpired {
+        market: Pubkey,
+        amount: u64,
+    },
+    DecisionDelegated {
+        market: Pubkey,
+        delegate: Pubkey,
+        expires_slot: Slot,
+    },
+    /// Fees claimed from a fee vault; `referrer` is the default pubkey for protocol fees.
+    FeesClaimed {
+        referrer: Pubkey,
+        amount: u64,
+    },
+    /// The config was created or its settings changed.
+    ConfigUpdated {
+        params: ConfigParams,
+    },
+    PausedSet {
+        paused: bool,
+    },
+    AdminProposed {
+        admin: Pubkey,
+        pending_admin: Pubkey,
+    },
+    AdminAccepted {
+        admin: Pubkey,
+    },
+}
+
+impl SearchMarketEvent {
+    pub(crate) fn emit(&self) -> ProgramResult {
+        let mut data = vec![EVENT_VERSION];
+        self.serialize(&mut data)?;
+        sol_log_data(&[&data]);
+        Ok(())
+    }
+
+    /// Decodes one field logged by `emit`.
+    pub fn decode(data: &[u8]) -> Result<SearchMarketEvent, Error> {
+        match data.split_first() {
+            Some((&EVENT_VERSION, event)) => SearchMarketEvent::try_from_slice(event),
+            Some((version, _)) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown event version {}", version),
+            )),
+            None => Err(Error::new(ErrorKind::UnexpectedEof, "empty event")),
+        }
+    }
+
+    /// Decodes the event on a program log line, `None` if the line is not a data line.
+    pub fn from_log(line: &str) -> Option<Result<SearchMarketEvent, Error>> {
+        let encoded = line.strip_prefix(EVENT_LOG_PREFIX)?;
+        Some(
+            base64::decode(encoded)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+                .and_then(|data| SearchMarketEvent::decode(&data)),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_event_round_trip() {
+        let event = SearchMarketEvent::Decided {
+            market: Pubkey::new_unique(),
+            best_result: Pubkey::new_unique(),
+            payouts: vec![(Pubkey::new_unique(), 7_000), (Pubkey::new_unique(), 3_000)],
+        };
+        let mut data = vec![EVENT_VERSION];
+        event.serialize(&mut data).unwrap();
+        assert_eq!(SearchMarketEvent::decode(&data).unwrap(), event);
+
+        let line = format!("{}{}", EVENT_LOG_PREFIX, base64::encode(&data));
+        assert_eq!(SearchMarketEvent::from_log(&line).unwrap().unwrap(), event);
+        assert!(SearchMarketEvent::from_log("Program log: hello").is_none());
+
+        data[0] = EVENT_VERSION + 1;
+        assert!(SearchMarketEvent::decode(&data).is_err());
+        assert!(SearchMarketEvent::decode(&[]).is_err());
+    }
+}
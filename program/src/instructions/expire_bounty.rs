@@ -0,0 +1,173 @@
+use super::{
+    transfer_program_lamports, SearchMarketAccount, SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub fn expire_bounty_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    fee_destination_pubkey: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::ExpireBounty.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(*fee_destination_pubkey, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Sends the whole bounty of a market whose decision deadline passed without a decision to its fee
+/// destination. Anyone may crank this; the decision authority forfeits its refund by not deciding
+/// in time.
+pub fn expire_bounty(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let fee_destination_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+
+    if market.fee_destination != *fee_destination_info.key {
+        return Err(SearchMarketError::FeeDestinationMismatch.into());
+    }
+
+    if clock.slot <= market.decision_deadline {
+        msg!("Decision deadline is slot {}", market.decision_deadline);
+        return Err(SearchMarketError::MarketNotExpired.into());
+    }
+
+    if market.bounty == 0 {
+        return Err(SearchMarketError::BountyAlreadyPaid.into());
+    }
+
+    transfer_program_lamports(market_account_info, fee_destination_info, market.bounty)?;
+    let amount = market.bounty;
+    market.bounty = 0;
+
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::BountyExpired {
+        market: *market_account_info.key,
+        amount,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use crate::test_utils::*;
+    use solana_program_test::{processor, ProgramTest};
+    use solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+
+    #[tokio::test]
+    async fn test_expire_bounty() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        market.bounty = 1_000_000;
+        market.bounty_refund_bps = 2_500;
+        market.fee_destination = Pubkey::new_unique();
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+
+        let expire_bounty =
+            expire_bounty_instruction(&program_id, &market_key, &market.fee_destination).unwrap();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&[create_market], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &decision_authority],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[expire_bounty.clone()], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer], context.last_blockhash);
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::MarketNotExpired as u32)
+            )
+        );
+
+        context.warp_to_slot(5).unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[expire_bounty], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer], recent_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let market_account = context
+            .banks_client
+            .get_account(market_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let processed_market =
+            SearchMarketAccount::try_from_slice(&market_account.data[..]).unwrap();
+        assert_eq!(processed_market.bounty, 0);
+        assert_eq!(
+            market_account.lamports,
+            minimum_balance(&processed_market).unwrap()
+        );
+
+        let fee_destination = context
+            .banks_client
+            .get_account(market.fee_destination)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee_destination.lamports, 1_000_000);
+    }
+}
@@ -1,44 +1,65 @@
-use super::{OrderAccount, OrderSide, SearchMarketInstruction};
+use super::{
+    fee_vault_address, find_book_address, find_config_address, find_fee_vault_address,
+    maker_account_metas, match_resting_orders, pair_mint_account_metas, ConfigAccount, MarketState,
+    OrderAccount, OrderBookAccount, OrderSide, PairMint, ResultAccount, SearchMarketAccount,
+    SearchMarketInstruction,
+};
+use crate::SearchMarketError;
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
-    program::invoke_signed,
+    msg,
+    program::set_return_data,
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction, system_program,
+    system_program,
+    sysvar::Sysvar,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn fill_order_instruction(
     program_id: &Pubkey,
-    buy_order: &Pubkey,
-    buyer_token_account: &Pubkey,
-    sell_order: &Pubkey,
-    seller_sol_account: &Pubkey,
-    execution_authority: &Pubkey,
+    search_market: &Pubkey,
+    result: &Pubkey,
+    sol_account: &Pubkey,
+    token_account: &Pubkey,
+    token_authority_account: &Pubkey,
+    referrer: Option<Pubkey>,
+    side: OrderSide,
+    limit_price: u64,
+    quantity: u64,
+    makers: &[(Pubkey, OrderAccount)],
 ) -> Result<Instruction, std::io::Error> {
-    let (sol_escrow, sol_escrow_bump_seed) =
-        Pubkey::find_program_address(&[b"sol_escrow", &buy_order.to_bytes()], program_id);
-    let (token_escrow, token_escrow_bump_seed) =
-        Pubkey::find_program_address(&[b"token_escrow", &sell_order.to_bytes()], program_id);
-
+    let (book, _book_bump_seed) = find_book_address(program_id, result);
+    let (fee_vault, fee_vault_bump_seed) =
+        find_fee_vault_address(program_id, &referrer.unwrap_or_default());
     let data = SearchMarketInstruction::FillOrder {
-        sol_escrow_bump_seed,
-        token_escrow_bump_seed,
+        side: side as u8,
+        limit_price,
+        quantity,
+        referrer,
+        fee_vault_bump_seed,
     }
     .try_to_vec()?;
-    let accounts = vec![
-        AccountMeta::new(*buy_order, false),
-        AccountMeta::new(*buyer_token_account, false),
-        AccountMeta::new(*sell_order, false),
-        AccountMeta::new(*seller_sol_account, false),
-        AccountMeta::new(sol_escrow, false),
-        AccountMeta::new(token_escrow, false),
-        AccountMeta::new_readonly(*execution_authority, true),
+    let mut accounts = vec![
+        AccountMeta::new(book, false),
+        AccountMeta::new_readonly(*search_market, false),
+        AccountMeta::new(*result, false),
+        AccountMeta::new(*sol_account, true),
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new_readonly(*token_authority_account, side == OrderSide::Sell),
+        AccountMeta::new(fee_vault, false),
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
     ];
+    accounts.extend(pair_mint_account_metas(program_id, search_market, result));
+    for (maker_key, maker) in makers {
+        accounts.extend(maker_account_metas(program_id, maker_key, maker));
+    }
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -46,112 +67,127 @@ pub fn fill_order_instruction(
     })
 }
 
+/// Written as return data by `fill_order`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FillSummary {
+    pub quantity: u64,
+    pub average_price: u64,
+    pub fees: u64,
+}
+
+/// Sweeps the book with an immediate-or-cancel taker order, filling against the resting orders
+/// passed as remaining accounts until the limit price or quantity is reached.
 pub fn fill_order(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    sol_escrow_bump_seed: u8,
-    token_escrow_bump_seed: u8,
+    side: u8,
+    limit_price: u64,
+    quantity: u64,
+    referrer: Option<Pubkey>,
+    fee_vault_bump_seed: u8,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let buy_order_account_info = next_account_info(account_info_iter)?;
-    let buyer_token_account_info = next_account_info(account_info_iter)?;
-    let sell_order_account_info = next_account_info(account_info_iter)?;
-    let seller_sol_account_info = next_account_info(account_info_iter)?;
-    let sol_escrow_account_info = next_account_info(account_info_iter)?;
-    let token_escrow_account_info = next_account_info(account_info_iter)?;
-    let execution_authority_account_info = next_account_info(account_info_iter)?;
+    let book_account_info = next_account_info(account_info_iter)?;
+    let market_account_info = next_account_info(account_info_iter)?;
+    let result_account_info = next_account_info(account_info_iter)?;
+    let sol_account_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+    let token_authority_account_info = next_account_info(account_info_iter)?;
+    let fee_vault_account_info = next_account_info(account_info_iter)?;
     let spl_token_program_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let yes_mint_account_info = next_account_info(account_info_iter)?;
+    let no_mint_account_info = next_account_info(account_info_iter)?;
 
-    let mut buy_order =
-        OrderAccount::try_from_slice(&buy_order_account_info.data.borrow()).unwrap();
-    let mut sell_order =
-        OrderAccount::try_from_slice(&sell_order_account_info.data.borrow()).unwrap();
+    let config = ConfigAccount::load_unpaused(program_id, config_account_info)?;
 
-    let sol_escrow_seeds: &[&[u8]] = &[
-        b"sol_escrow",
-        &buy_order_account_info.key.to_bytes(),
-        &[sol_escrow_bump_seed],
-    ];
-    let sol_escrow_key = Pubkey::create_program_address(sol_escrow_seeds, program_id).unwrap();
-    if *sol_escrow_account_info.key != sol_escrow_key {
-        return Err(ProgramError::InvalidAccountData);
+    if side > OrderSide::BuyNo as u8 {
+        return Err(SearchMarketError::InvalidOrderSide.into());
     }
+    let side: OrderSide = side.into();
 
-    let token_escrow_seeds: &[&[u8]] = &[
-        b"token_escrow",
-        &sell_order_account_info.key.to_bytes(),
-        &[token_escrow_bump_seed],
-    ];
-    let token_escrow_key = Pubkey::create_program_address(token_escrow_seeds, program_id).unwrap();
-    if *token_escrow_account_info.key != token_escrow_key {
-        return Err(ProgramError::InvalidAccountData);
+    if !sol_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
-    if buy_order.price < sell_order.price {
-        return Err(ProgramError::InvalidAccountData);
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
     }
 
-    if !(*execution_authority_account_info.key == buy_order.execution_authority
-        || *execution_authority_account_info.key == sell_order.execution_authority)
+    if fee_vault_address(program_id, &referrer, fee_vault_bump_seed)? != *fee_vault_account_info.key
     {
-        return Err(ProgramError::MissingRequiredSignature);
+        return Err(SearchMarketError::InvalidAccountAddress.into());
     }
 
-    let price = if buy_order.creation_slot <= sell_order.creation_slot {
-        buy_order.price
-    } else {
-        sell_order.price
-    };
-    let quantity = if buy_order.quantity <= sell_order.quantity {
-        buy_order.quantity
-    } else {
-        sell_order.quantity
-    };
-
-    invoke_signed(
-        &system_instruction::transfer(
-            sol_escrow_account_info.key,
-            seller_sol_account_info.key,
-            price * quantity,
-        ),
-        &[
-            sol_escrow_account_info.clone(),
-            seller_sol_account_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[sol_escrow_seeds],
+    if *market_account_info.owner != *program_id
+        || *result_account_info.owner != *program_id
+        || *book_account_info.owner != *program_id
+    {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+    if market.state(Clock::get()?.slot)? != MarketState::Open {
+        msg!("trading closed at slot {}", market.expires_slot);
+        return Err(SearchMarketError::TradingClosed.into());
+    }
+    if !market.valid_price(limit_price) {
+        msg!(
+            "limit price {} invalid for tick {}",
+            limit_price,
+            market.tick_size
+        );
+        return Err(SearchMarketError::InvalidPrice.into());
+    }
+    let mut result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.search_market != *market_account_info.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+    let mut book = OrderBookAccount::unpack(&book_account_info.data.borrow())?;
+    if book.result != *result_account_info.key {
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    let mut pair_mint = PairMint::new(
+        program_id,
+        market_account_info.key,
+        &market,
+        &result,
+        vault_account_info,
+        yes_mint_account_info,
+        no_mint_account_info,
     )?;
 
-    invoke_signed(
-        &spl_token::instruction::transfer(
-            &spl_token::id(),
-            &token_escrow_account_info.key,
-            &buyer_token_account_info.key,
-            &token_escrow_account_info.key,
-            &[],
-            quantity,
-        )
-        .unwrap(),
-        &[
-            token_escrow_account_info.clone(),
-            buyer_token_account_info.clone(),
-            token_escrow_account_info.clone(),
-            spl_token_program_info.clone(),
-        ],
-        &[token_escrow_seeds],
+    let (filled, cost, fees) = match_resting_orders(
+        program_id,
+        &mut book,
+        side,
+        side.book_price(limit_price)?,
+        quantity,
+        sol_account_info.key,
+        sol_account_info,
+        token_account_info,
+        token_authority_account_info,
+        fee_vault_account_info,
+        spl_token_program_info,
+        system_program_info,
+        config.params.taker_fee_bps,
+        &mut pair_mint,
+        account_info_iter,
     )?;
+    pair_mint.record_collateral(&mut result, result_account_info)?;
+    book.serialize(&mut &mut book_account_info.data.borrow_mut()[..])?;
 
-    buy_order.quantity -= quantity;
-    buy_order
-        .serialize(&mut &mut buy_order_account_info.data.borrow_mut()[..])
-        .unwrap();
-
-    sell_order.quantity -= quantity;
-    sell_order
-        .serialize(&mut &mut sell_order_account_info.data.borrow_mut()[..])
-        .unwrap();
-
+    let summary = FillSummary {
+        quantity: filled,
+        average_price: cost.checked_div(filled).unwrap_or(0),
+        fees,
+    };
+    msg!("Filled {:?}", summary);
+    set_return_data(&summary.try_to_vec()?);
     Ok(())
 }
 
@@ -161,26 +197,34 @@ pub mod test {
     use super::*;
     use crate::instructions::test_utils::*;
     use crate::process_instruction;
-    use crate::{ResultAccount, SearchMarketAccount};
+    use crate::test_utils::*;
+    use crate::{ResultAccount, SearchMarketAccount, LAMPORTS_PER_TOKEN};
     use solana_program::program_pack::Pack;
     use solana_program_test::{processor, ProgramTest};
     use solana_sdk::{
         account::Account as SolanaAccount,
+        instruction::InstructionError,
         rent::Rent,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
 
     #[tokio::test]
-    async fn test_fill_order() {
+    async fn test_fill_order_sweep() {
         let program_id = crate::id();
         let mut program_test =
             ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
         let decision_authority = Keypair::new();
-        let market =
-            SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 2);
-        let (market_key, create_market) = setup_market(&market, 1, &mut program_test, &program_id);
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
 
         let mut result = ResultAccount::new(
             market_key,
@@ -189,9 +233,9 @@ pub mod test {
             String::from("A game fated to be legend"),
             Pubkey::new_unique(),
             Pubkey::new_unique(),
-            0,
         );
-        let (result_key, create_result) = setup_result(&mut result, &mut program_test, &program_id);
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
         let deposit_keypair = Keypair::new();
         let (yes_token_pubkey, init_yes_token) = setup_token(
@@ -218,73 +262,93 @@ pub mod test {
             &program_id,
         );
 
-        let seller_sol_keypair = Keypair::new();
-        let seller_sol_account = SolanaAccount::new(
-            Rent::default().minimum_balance(0)
-                + Rent::default().minimum_balance(spl_token::state::Account::LEN),
-            0,
-            &system_program::id(),
-        );
-        program_test.add_account(seller_sol_keypair.pubkey(), seller_sol_account);
-        let mut sell_order = OrderAccount::new(
-            market_key,
-            result_key,
-            seller_sol_keypair.pubkey(),
-            yes_token_pubkey,
-            OrderSide::Sell,
-            500,
-            100,
-            0,
-            1,
-            deposit_keypair.pubkey(),
-        );
-        let (sell_order_key, sell_escrow_key, create_sell_order) = setup_order(
-            &mut sell_order,
-            &result.yes_mint,
-            &deposit_keypair.pubkey(),
-            &mut program_test,
-            &program_id,
-        );
+        let taker_sol_keypair = Keypair::new();
+        let taker_sol_account =
+            SolanaAccount::new(Rent::default().minimum_balance(0), 0, &system_program::id());
+        program_test.add_account(taker_sol_keypair.pubkey(), taker_sol_account);
 
-        let buyer_sol_keypair = Keypair::new();
-        let buyer_sol_account = SolanaAccount::new(
-            2 * Rent::default().minimum_balance(0) + 500 * 100,
-            0,
-            &system_program::id(),
-        );
-        program_test.add_account(buyer_sol_keypair.pubkey(), buyer_sol_account);
-        let (buy_token_pubkey, init_buy_token) = setup_token(
-            &result.yes_mint,
+        let mut makers = vec![];
+        let mut create_bids = vec![];
+        let mut maker_token_pubkeys = vec![];
+        for price in [520, 500] {
+            let maker_keypair = Keypair::new();
+            let (maker_token_pubkey, init_maker_token) =
+                setup_token(&result.yes_mint, &maker_keypair.pubkey(), &mut program_test);
+            let mut bid = OrderAccount::new(
+                market_key,
+                result_key,
+                maker_keypair.pubkey(),
+                maker_keypair.pubkey(),
+                maker_token_pubkey,
+                OrderSide::Buy,
+                price,
+                30,
+                0,
+                1,
+                maker_keypair.pubkey(),
+            );
+            let (bid_key, bid_escrow_key, create_bid) = setup_order(
+                &mut bid,
+                &result.yes_mint,
+                &maker_keypair.pubkey(),
+                &program_id,
+            );
+            let maker_sol_account = SolanaAccount::new(
+                price * 30 + Rent::default().minimum_balance(0) + minimum_balance(&bid).unwrap(),
+                0,
+                &system_program::id(),
+            );
+            program_test.add_account(maker_keypair.pubkey(), maker_sol_account);
+            create_bids.push((maker_keypair, init_maker_token, create_bid));
+            maker_token_pubkeys.push(maker_token_pubkey);
+            makers.push((bid_key, bid_escrow_key, bid));
+        }
+        let maker_accounts: Vec<(Pubkey, OrderAccount)> = makers
+            .iter()
+            .map(|(bid_key, _bid_escrow_key, bid)| (*bid_key, bid.clone()))
+            .collect();
+
+        let frontend = Pubkey::new_unique();
+        let sweep = fill_order_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &taker_sol_keypair.pubkey(),
+            &yes_token_pubkey,
             &deposit_keypair.pubkey(),
-            &mut program_test,
-        );
-        let mut buy_order = OrderAccount::new(
-            market_key,
-            result_key,
-            buyer_sol_keypair.pubkey(),
-            buy_token_pubkey,
-            OrderSide::Buy,
-            501,
+            Some(frontend),
+            OrderSide::Sell,
+            510,
             100,
-            0,
-            1,
-            deposit_keypair.pubkey(),
-        );
-        let (buy_order_key, buy_escrow_key, create_buy_order) = setup_order(
-            &mut buy_order,
-            &result.yes_mint,
-            &deposit_keypair.pubkey(),
-            &mut program_test,
+            &maker_accounts,
+        )
+        .unwrap();
+        let partial_sweep = fill_order_instruction(
             &program_id,
-        );
-
-        let fill_order = fill_order_instruction(
+            &market_key,
+            &result_key,
+            &taker_sol_keypair.pubkey(),
+            &yes_token_pubkey,
+            &deposit_keypair.pubkey(),
+            Some(frontend),
+            OrderSide::Sell,
+            500,
+            10,
+            &maker_accounts[1..],
+        )
+        .unwrap();
+        let overpriced = fill_order_instruction(
             &program_id,
-            &buy_order_key,
-            &buy_order.token_account,
-            &sell_order_key,
-            &sell_order.sol_account,
-            &buy_order.execution_authority,
+            &market_key,
+            &result_key,
+            &taker_sol_keypair.pubkey(),
+            &yes_token_pubkey,
+            &deposit_keypair.pubkey(),
+            Some(frontend),
+            OrderSide::BuyNo,
+            LAMPORTS_PER_TOKEN + 10,
+            10,
+            &maker_accounts,
         )
         .unwrap();
 
@@ -308,58 +372,118 @@ pub mod test {
             .await
             .unwrap();
 
-        let mut transaction = Transaction::new_with_payer(
-            &[
-                create_sell_order,
-                init_buy_token,
-                create_buy_order,
-                fill_order,
-            ],
-            Some(&payer.pubkey()),
+        for (maker_keypair, init_maker_token, create_bid) in create_bids {
+            let mut transaction =
+                Transaction::new_with_payer(&[init_maker_token, create_bid], Some(&payer.pubkey()));
+            transaction.sign(&[&payer, &maker_keypair], recent_blockhash);
+            banks_client.process_transaction(transaction).await.unwrap();
+        }
+
+        let mut transaction = Transaction::new_with_payer(&[overpriced], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &taker_sol_keypair], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidPrice as u32)
+            )
         );
+
+        let mut transaction = Transaction::new_with_payer(&[sweep], Some(&payer.pubkey()));
         transaction.sign(
-            &[
-                &payer,
-                &deposit_keypair,
-                &buyer_sol_keypair,
-                &seller_sol_keypair,
-            ],
+            &[&payer, &taker_sol_keypair, &deposit_keypair],
             recent_blockhash,
         );
         banks_client.process_transaction(transaction).await.unwrap();
 
-        let seller_sol_account = banks_client
-            .get_account(seller_sol_keypair.pubkey())
+        let (best_bid_key, best_bid_escrow_key, best_bid) = &makers[0];
+        assert_eq!(banks_client.get_account(*best_bid_key).await.unwrap(), None);
+        assert_eq!(
+            banks_client
+                .get_account(*best_bid_escrow_key)
+                .await
+                .unwrap(),
+            None
+        );
+        let best_bid_sol_account = banks_client
+            .get_account(best_bid.sol_account)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            best_bid_sol_account.lamports,
+            Rent::default().minimum_balance(0) + minimum_balance(best_bid).unwrap()
+        );
+        let best_bid_token_account = banks_client
+            .get_account(maker_token_pubkeys[0])
+            .await
+            .unwrap()
+            .unwrap();
+        let best_bid_token =
+            spl_token::state::Account::unpack(&best_bid_token_account.data).unwrap();
+        assert_eq!(best_bid_token.amount, 30);
+
+        let taker_sol_account = banks_client
+            .get_account(taker_sol_keypair.pubkey())
             .await
             .unwrap()
             .unwrap();
         assert_eq!(
-            seller_sol_account.lamports,
-            (501 * 100) + Rent::default().minimum_balance(0)
+            taker_sol_account.lamports,
+            Rent::default().minimum_balance(0) + 520 * 30 - 46
         );
 
-        let buy_token_account = banks_client
-            .get_account(buy_token_pubkey)
+        let mut transaction = Transaction::new_with_payer(&[partial_sweep], Some(&payer.pubkey()));
+        transaction.sign(
+            &[&payer, &taker_sol_keypair, &deposit_keypair],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (other_bid_key, _other_bid_escrow_key, _other_bid) = &makers[1];
+        let other_bid_account = banks_client
+            .get_account(*other_bid_key)
             .await
             .unwrap()
             .unwrap();
-        let buy_token = spl_token::state::Account::unpack(&buy_token_account.data).unwrap();
-        assert_eq!(buy_token.amount, 100);
+        let other_bid = OrderAccount::try_from_slice(&other_bid_account.data).unwrap();
+        assert_eq!(other_bid.quantity, 20);
+
+        let (book_key, _book_bump_seed) = find_book_address(&program_id, &result_key);
+        let book_account = banks_client.get_account(book_key).await.unwrap().unwrap();
+        let book = OrderBookAccount::unpack(&book_account.data).unwrap();
+        assert_eq!(book.bids.len(), 1);
+        assert_eq!(book.bids[0].order, *other_bid_key);
+        assert_eq!(book.bids[0].quantity, 20);
 
-        let buy_order_account = banks_client
-            .get_account(buy_order_key)
+        let yes_token_account = banks_client
+            .get_account(yes_token_pubkey)
             .await
             .unwrap()
             .unwrap();
-        let buy_order = OrderAccount::try_from_slice(&buy_order_account.data).unwrap();
-        assert_eq!(buy_order.quantity, 0);
+        let yes_token = spl_token::state::Account::unpack(&yes_token_account.data).unwrap();
+        assert_eq!(yes_token.amount, 60);
+
+        let taker_sol_account = banks_client
+            .get_account(taker_sol_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            taker_sol_account.lamports,
+            Rent::default().minimum_balance(0) + 520 * 30 + 500 * 10 - 46 - 15
+        );
 
-        let sell_order_account = banks_client
-            .get_account(sell_order_key)
+        let (fee_vault_key, _fee_vault_bump_seed) = find_fee_vault_address(&program_id, &frontend);
+        let fee_vault_account = banks_client
+            .get_account(fee_vault_key)
             .await
             .unwrap()
             .unwrap();
-        let sell_order = OrderAccount::try_from_slice(&sell_order_account.data).unwrap();
-        assert_eq!(sell_order.quantity, 0);
+        assert_eq!(fee_vault_account.lamports, 46 + 15);
     }
 }
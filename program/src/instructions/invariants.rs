@@ -0,0 +1,119 @@
+//! Solvency checks compiled in with the `debug-invariants` feature. They re-derive what an escrow
+//! or the vault must hold from the state just written and fail the instruction if it falls short.
+
+use super::create_order::buy_escrow_amount;
+use super::{OrderAccount, OrderSide, PoolAccount, ResultAccount, SearchMarketAccount};
+use crate::{SearchMarketError, LAMPORTS_PER_TOKEN};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent, sysvar::Sysvar,
+};
+use spl_token::state::{Account, Mint};
+
+pub(crate) fn check_order_escrow(order: &OrderAccount, escrow: &AccountInfo) -> ProgramResult {
+    match order.side.into() {
+        OrderSide::Buy | OrderSide::BuyNo => {
+            let rent = Rent::get()?;
+            let required = buy_escrow_amount(order.price, order.quantity, order.maker_fee_bps)?
+                .checked_add(rent.minimum_balance(0))
+                .ok_or(SearchMarketError::ArithmeticOverflow)?;
+            if escrow.lamports() < required {
+                msg!("sol escrow {} below {}", escrow.lamports(), required);
+                return Err(SearchMarketError::InvariantViolated.into());
+            }
+        }
+        OrderSide::Sell => {
+            let held = Account::unpack(&escrow.data.borrow())?.amount;
+            if held < order.quantity {
+                msg!("token escrow {} below {}", held, order.quantity);
+                return Err(SearchMarketError::InvariantViolated.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Every token that can still be redeemed must be backed by the result's collateral, which in
+/// turn must be sitting in the vault on top of its rent-exempt reserve. Unpaired yes tokens are backed by the market's pool while
+/// trading, and once settled any redemption may draw on what is left of the pool.
+pub(crate) fn check_result_backing(
+    market: &SearchMarketAccount,
+    result_key: &Pubkey,
+    result: &ResultAccount,
+    vault: &AccountInfo,
+    yes_mint: &AccountInfo,
+    no_mint: &AccountInfo,
+) -> ProgramResult {
+    let yes_supply = Mint::unpack(&yes_mint.data.borrow())?.supply;
+    let no_supply = Mint::unpack(&no_mint.data.borrow())?.supply;
+    if !market.is_settled() {
+        let required = yes_supply
+            .saturating_sub(result.unpaired_yes)
+            .max(no_supply)
+            .checked_mul(LAMPORTS_PER_TOKEN)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        return check_collateral(result, vault, required, 0);
+    }
+    let (yes_value, no_value) = market.redemption_values(result_key, result);
+    let required = yes_supply
+        .checked_mul(yes_value)
+        .zip(no_supply.checked_mul(no_value))
+        .and_then(|(yes, no)| yes.checked_add(no))
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    check_collateral(result, vault, required, market.pooled_collateral)
+}
+
+/// Partial check for a settled market, covering only the tokens of `mint` worth `token_value`
+/// lamports each.
+pub(crate) fn check_settled_backing(
+    market: &SearchMarketAccount,
+    result: &ResultAccount,
+    vault: &AccountInfo,
+    mint: &AccountInfo,
+    token_value: u64,
+) -> ProgramResult {
+    let required = Mint::unpack(&mint.data.borrow())?
+        .supply
+        .checked_mul(token_value)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    check_collateral(result, vault, required, market.pooled_collateral)
+}
+
+fn check_collateral(
+    result: &ResultAccount,
+    vault: &AccountInfo,
+    required: u64,
+    pooled: u64,
+) -> ProgramResult {
+    let available = result.collateral.saturating_add(pooled);
+    let held = vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0));
+    if available < required || held < available {
+        msg!(
+            "collateral {} vault {} required {}",
+            available,
+            held,
+            required
+        );
+        return Err(SearchMarketError::InvariantViolated.into());
+    }
+    Ok(())
+}
+
+/// A pool must hold the reserves it trades from: its lamports on top of its rent and its yes
+/// tokens in its token account.
+pub(crate) fn check_pool_reserves(
+    pool: &PoolAccount,
+    pool_account: &AccountInfo,
+    pool_tokens: &AccountInfo,
+) -> ProgramResult {
+    let rent = Rent::get()?.minimum_balance(pool_account.data_len());
+    let sol_held = pool_account.lamports().saturating_sub(rent);
+    let yes_held = Account::unpack(&pool_tokens.data.borrow())?.amount;
+    if sol_held < pool.sol_reserve || yes_held < pool.yes_reserve {
+        msg!("pool holds {} lamports {} yes", sol_held, yes_held);
+        return Err(SearchMarketError::InvariantViolated.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Fixed-point math for the logarithmic market scoring rule that prices each result's market
+//! maker. With liquidity `b` and `yes` and `no` tokens sold, the maker's cost function is
+//! `C = b ln(e^(yes / b) + e^(no / b))` tokens. A trade pays the change in `C`, so the maker can
+//! lose at most `b ln 2` tokens, which `subsidy` funds.
+
+use crate::LAMPORTS_PER_TOKEN;
+use std::convert::TryFrom;
+
+/// One in the fixed-point representation used for intermediate values.
+const ONE: i128 = 1_000_000_000_000_000_000;
+const ONE_PER_LAMPORT: i128 = ONE / LAMPORTS_PER_TOKEN as i128;
+const LN_2: i128 = 693_147_180_559_945_309;
+/// `e^-x` is below the fixed-point resolution from here on.
+const EXP_NEG_CUTOFF: i128 = 42 * ONE;
+
+/// `e^-x` for `x >= 0`, reduced to `2^-k e^-r` with `r < ln 2` for the series.
+fn exp_neg(x: i128) -> i128 {
+    if x >= EXP_NEG_CUTOFF {
+        return 0;
+    }
+    let halvings = x / LN_2;
+    let r = x - halvings * LN_2;
+    let mut term = ONE;
+    let mut sum = ONE;
+    let mut n = 1;
+    while term != 0 {
+        term = term * r / ONE / n;
+        if n % 2 == 1 {
+            sum -= term;
+        } else {
+            sum += term;
+        }
+        n += 1;
+    }
+    sum >> halvings
+}
+
+/// `ln(1 + z)` for `0 <= z <= 1`, through `2 atanh(z / (2 + z))`.
+fn ln_1p(z: i128) -> i128 {
+    let t = z * ONE / (2 * ONE + z);
+    let t_squared = t * t / ONE;
+    let mut power = t;
+    let mut sum = 0;
+    let mut n = 1;
+    while power != 0 {
+        sum += power / n;
+        power = power * t_squared / ONE;
+        n += 2;
+    }
+    2 * sum
+}
+
+/// `ln(1 + e^x)`.
+fn softplus(x: i128) -> i128 {
+    x.max(0) + ln_1p(exp_neg(x.abs()))
+}
+
+/// `(yes - no) / b` in fixed point.
+fn spread(liquidity: u64, yes: i64, no: i64) -> Option<i128> {
+    if liquidity == 0 {
+        return None;
+    }
+    (yes as i128 - no as i128)
+        .checked_mul(ONE)
+        .map(|spread| spread / liquidity as i128)
+}
+
+/// `C(new) - C(old)` in fixed-point tokens, using `C = no + b softplus((yes - no) / b)`.
+fn cost_change(liquidity: u64, yes: i64, no: i64, new_yes: i64, new_no: i64) -> Option<i128> {
+    let old = softplus(spread(liquidity, yes, no)?);
+    let new = softplus(spread(liquidity, new_yes, new_no)?);
+    (new_no as i128 - no as i128)
+        .checked_mul(ONE)?
+        .checked_add((new - old).checked_mul(liquidity as i128)?)
+}
+
+/// Lamports the creator sets aside for a result's market maker: its worst case loss, rounded up
+/// with a lamport to spare so approximation error never leaves tokens unbacked.
+pub fn subsidy(liquidity: u64) -> Option<u64> {
+    let loss = (liquidity as i128).checked_mul(LN_2)?;
+    u64::try_from((loss + ONE_PER_LAMPORT - 1) / ONE_PER_LAMPORT + 1).ok()
+}
+
+/// Lamports a trader pays to move the maker from `yes`, `no` to `new_yes`, `new_no`, rounded up.
+pub fn buy_cost(liquidity: u64, yes: i64, no: i64, new_yes: i64, new_no: i64) -> Option<u64> {
+    let cost = cost_change(liquidity, yes, no, new_yes, new_no)?;
+    u64::try_from((cost.max(0) + ONE_PER_LAMPORT - 1) / ONE_PER_LAMPORT).ok()
+}
+
+/// Lamports a trader receives to move the maker from `yes`, `no` to `new_yes`, `new_no`, rounded
+/// down.
+pub fn sell_proceeds(liquidity: u64, yes: i64, no: i64, new_yes: i64, new_no: i64) -> Option<u64> {
+    let proceeds = cost_change(liquidity, yes, no, new_yes, new_no)?.checked_neg()?;
+    u64::try_from(proceeds.max(0) / ONE_PER_LAMPORT).ok()
+}
+
+/// Lamports the maker quotes a yes token at, `e^(yes / b) / (e^(yes / b) + e^(no / b))` of a
+/// token's collateral.
+pub fn yes_price(liquidity: u64, yes: i64, no: i64) -> Option<u64> {
+    let spread = spread(liquidity, yes, no)?;
+    let e = exp_neg(spread.abs());
+    let probability = if spread >= 0 {
+        ONE * ONE / (ONE + e)
+    } else {
+        e * ONE / (ONE + e)
+    };
+    u64::try_from(probability / ONE_PER_LAMPORT).ok()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+
+    fn to_f64(value: i128) -> f64 {
+        value as f64 / ONE as f64
+    }
+
+    #[test]
+    fn test_exp_and_ln() {
+        for x in [0.0, 0.001, 0.5, 0.693, 1.0, 2.5, 10.0, 30.0] {
+            let fixed = (x * ONE as f64) as i128;
+            assert!((to_f64(exp_neg(fixed)) - (-x).exp()).abs() < 1e-12);
+        }
+        for z in [0.0, 0.001, 0.25, 0.5, 0.999, 1.0] {
+            let fixed = (z * ONE as f64) as i128;
+            assert!((to_f64(ln_1p(fixed)) - z.ln_1p()).abs() < 1e-12);
+        }
+        assert_eq!(exp_neg(EXP_NEG_CUTOFF), 0);
+    }
+
+    #[test]
+    fn test_prices_and_costs() {
+        let b = 100;
+        assert_eq!(yes_price(b, 0, 0), Some(LAMPORTS_PER_TOKEN / 2));
+        assert!(yes_price(b, 50, 0).unwrap() > LAMPORTS_PER_TOKEN / 2);
+        assert!(yes_price(b, 0, 50).unwrap() < LAMPORTS_PER_TOKEN / 2);
+        assert_eq!(yes_price(0, 0, 0), None);
+        assert_eq!(subsidy(b), Some(6_931_473));
+
+        // A yes and a no together are worth exactly one token wherever the maker stands.
+        let yes_cost = buy_cost(b, 0, 0, 10, 0).unwrap();
+        let no_cost = buy_cost(b, 10, 0, 10, 10).unwrap();
+        let total = yes_cost + no_cost;
+        assert!((10 * LAMPORTS_PER_TOKEN..=10 * LAMPORTS_PER_TOKEN + 2).contains(&total));
+
+        // Selling back never returns more than was paid.
+        assert!(sell_proceeds(b, 10, 0, 0, 0).unwrap() <= yes_cost);
+
+        // However far the maker is pushed, what it collected plus the subsidy covers its tokens.
+        let cost = buy_cost(b, 0, 0, 5_000, 0).unwrap();
+        assert!(cost + subsidy(b).unwrap() >= 5_000 * LAMPORTS_PER_TOKEN);
+        let cost = buy_cost(b, 0, 0, 0, 50).unwrap();
+        assert!(cost + subsidy(b).unwrap() >= 50 * LAMPORTS_PER_TOKEN);
+    }
+}
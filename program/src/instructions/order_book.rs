@@ -0,0 +1,185 @@
+use super::{AccountType, OrderSide};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+
+pub const MAX_BOOK_ORDERS: usize = 64;
+
+pub fn find_book_address(program_id: &Pubkey, result: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"book", &result.to_bytes()], program_id)
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct BookEntry {
+    pub order: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub sequence: u64,
+}
+
+/// Resting yes token orders for a result. Bids are sorted by descending price and asks by
+/// ascending price, with ties kept in arrival order, so the first entry of each side is the best.
+/// Buys of no tokens rest among the asks at the yes price they leave for a complementary buyer.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OrderBookAccount {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub result: Pubkey,
+    pub next_sequence: u64,
+    pub bids: Vec<BookEntry>,
+    pub asks: Vec<BookEntry>,
+}
+
+impl OrderBookAccount {
+    pub fn new(result: Pubkey) -> OrderBookAccount {
+        OrderBookAccount {
+            account_type: AccountType::OrderBook as u8,
+            account_version: 0,
+            result,
+            next_sequence: 0,
+            bids: vec![],
+            asks: vec![],
+        }
+    }
+
+    /// Space needed for a book holding `MAX_BOOK_ORDERS` on each side.
+    pub fn space() -> usize {
+        let entry = 32 + 8 + 8 + 8;
+        1 + 1 + 32 + 8 + 2 * (4 + MAX_BOOK_ORDERS * entry)
+    }
+
+    /// Reads a book from its account data, ignoring the unused capacity at the end.
+    pub fn unpack(data: &[u8]) -> Result<OrderBookAccount, ProgramError> {
+        let mut data = data;
+        let book = OrderBookAccount::deserialize(&mut data)?;
+        if book.account_type != AccountType::OrderBook as u8 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(book)
+    }
+
+    pub fn entries(&self, side: OrderSide) -> &Vec<BookEntry> {
+        match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell | OrderSide::BuyNo => &self.asks,
+        }
+    }
+
+    fn entries_mut(&mut self, side: OrderSide) -> &mut Vec<BookEntry> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell | OrderSide::BuyNo => &mut self.asks,
+        }
+    }
+
+    pub fn best(&self, side: OrderSide) -> Option<&BookEntry> {
+        self.entries(side).first()
+    }
+
+    pub fn insert(
+        &mut self,
+        side: OrderSide,
+        order: Pubkey,
+        price: u64,
+        quantity: u64,
+    ) -> ProgramResult {
+        let sequence = self.next_sequence;
+        self.next_sequence = sequence
+            .checked_add(1)
+            .ok_or(SearchMarketError::ArithmeticOverflow)?;
+        let entries = self.entries_mut(side);
+        if entries.len() >= MAX_BOOK_ORDERS {
+            return Err(SearchMarketError::OrderBookFull.into());
+        }
+        let index = entries
+            .iter()
+            .position(|entry| match side {
+                OrderSide::Buy => entry.price < price,
+                OrderSide::Sell | OrderSide::BuyNo => entry.price > price,
+            })
+            .unwrap_or(entries.len());
+        entries.insert(
+            index,
+            BookEntry {
+                order,
+                price,
+                quantity,
+                sequence,
+            },
+        );
+        Ok(())
+    }
+
+    /// Takes `quantity` off a resting order, dropping it from the book once nothing is left.
+    pub fn fill(&mut self, side: OrderSide, order: &Pubkey, quantity: u64) -> ProgramResult {
+        let entries = self.entries_mut(side);
+        let index = entries
+            .iter()
+            .position(|entry| entry.order == *order)
+            .ok_or(SearchMarketError::OrderNotInBook)?;
+        if entries[index].quantity <= quantity {
+            entries.remove(index);
+        } else {
+            entries[index].quantity -= quantity;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, side: OrderSide, order: &Pubkey) -> ProgramResult {
+        let entries = self.entries_mut(side);
+        let index = entries
+            .iter()
+            .position(|entry| entry.order == *order)
+            .ok_or(SearchMarketError::OrderNotInBook)?;
+        entries.remove(index);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_book_price_time_priority() {
+        let mut book = OrderBookAccount::new(Pubkey::new_unique());
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let better = Pubkey::new_unique();
+        book.insert(OrderSide::Buy, first, 500, 10).unwrap();
+        book.insert(OrderSide::Buy, second, 500, 10).unwrap();
+        book.insert(OrderSide::Buy, better, 600, 10).unwrap();
+        let bids: Vec<Pubkey> = book.bids.iter().map(|entry| entry.order).collect();
+        assert_eq!(bids, vec![better, first, second]);
+
+        book.insert(OrderSide::Sell, first, 700, 10).unwrap();
+        book.insert(OrderSide::Sell, better, 650, 10).unwrap();
+        assert_eq!(book.best(OrderSide::Sell).unwrap().order, better);
+
+        book.fill(OrderSide::Buy, &better, 4).unwrap();
+        assert_eq!(book.best(OrderSide::Buy).unwrap().quantity, 6);
+        book.fill(OrderSide::Buy, &better, 6).unwrap();
+        assert_eq!(book.best(OrderSide::Buy).unwrap().order, first);
+
+        let mut data = vec![0; OrderBookAccount::space()];
+        book.serialize(&mut &mut data[..]).unwrap();
+        assert_eq!(OrderBookAccount::unpack(&data).unwrap(), book);
+    }
+
+    #[test]
+    fn test_book_full() {
+        let mut book = OrderBookAccount::new(Pubkey::new_unique());
+        for _ in 0..MAX_BOOK_ORDERS {
+            book.insert(OrderSide::Sell, Pubkey::new_unique(), 500, 1)
+                .unwrap();
+        }
+        assert_eq!(
+            book.insert(OrderSide::Sell, Pubkey::new_unique(), 500, 1),
+            Err(SearchMarketError::OrderBookFull.into())
+        );
+
+        let mut data = vec![0; OrderBookAccount::space()];
+        book.serialize(&mut &mut data[..]).unwrap();
+    }
+}
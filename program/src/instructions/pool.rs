@@ -0,0 +1,326 @@
+use super::{
+    create_pda_account, find_config_address, AccountType, ConfigAccount, MarketState,
+    ResultAccount, SearchMarketAccount, SearchMarketEvent, SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{rent, Sysvar},
+};
+use spl_token::state::{Account, Mint};
+
+/// A constant-product pool trading a result's yes tokens against SOL. The lamports it trades from
+/// sit on the pool account on top of its rent, and its yes tokens in the pool token account.
+/// Liquidity providers hold the LP mint's tokens, each a share of both reserves.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PoolAccount {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub search_market: Pubkey,
+    pub result: Pubkey,
+    pub yes_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub pool_tokens: Pubkey,
+    pub yes_reserve: u64,
+    pub sol_reserve: u64,
+    /// Taken from the config when the pool is created and charged on each swap's input, which
+    /// stays in the pool for its liquidity providers.
+    pub fee_bps: u16,
+    pub bump_seed: u8,
+}
+
+impl PoolAccount {
+    /// Lamports per yes token at the pool's current reserves, `None` while it is empty.
+    pub fn yes_price(&self) -> Option<u64> {
+        self.sol_reserve.checked_div(self.yes_reserve)
+    }
+
+    pub(crate) fn seeds<'a>(&'a self, bump_seed: &'a [u8; 1]) -> [&'a [u8]; 3] {
+        [b"pool", self.result.as_ref(), bump_seed]
+    }
+
+    /// Loads a pool owned by the program and checks the accounts it records.
+    pub(crate) fn load(
+        program_id: &Pubkey,
+        pool_account_info: &AccountInfo,
+        pool_tokens_account_info: &AccountInfo,
+        lp_mint_account_info: Option<&AccountInfo>,
+    ) -> Result<PoolAccount, ProgramError> {
+        if *pool_account_info.owner != *program_id {
+            return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+        }
+        let pool = PoolAccount::try_from_slice(&pool_account_info.data.borrow())?;
+        if pool.account_type != AccountType::Pool as u8 {
+            return Err(SearchMarketError::InvalidAccountAddress.into());
+        }
+        if pool.pool_tokens != *pool_tokens_account_info.key {
+            msg!("Pool token account does not match pool");
+            return Err(SearchMarketError::InvalidAccountAddress.into());
+        }
+        if let Some(lp_mint_account_info) = lp_mint_account_info {
+            if pool.lp_mint != *lp_mint_account_info.key {
+                return Err(SearchMarketError::MintMismatch.into());
+            }
+        }
+        Ok(pool)
+    }
+
+    /// Loads the pool's market and checks it is still trading.
+    pub(crate) fn load_open_market(
+        &self,
+        program_id: &Pubkey,
+        market_account_info: &AccountInfo,
+    ) -> ProgramResult {
+        if *market_account_info.owner != *program_id {
+            return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+        }
+        if self.search_market != *market_account_info.key {
+            return Err(SearchMarketError::ResultNotInMarket.into());
+        }
+        let market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+        if market.state(Clock::get()?.slot)? != MarketState::Open {
+            return Err(SearchMarketError::TradingClosed.into());
+        }
+        Ok(())
+    }
+}
+
+pub fn find_pool_address(program_id: &Pubkey, result: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool", &result.to_bytes()], program_id)
+}
+
+pub fn find_pool_tokens_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool_tokens", &pool.to_bytes()], program_id)
+}
+
+pub fn find_lp_mint_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"lp_mint", &pool.to_bytes()], program_id)
+}
+
+pub fn create_pool_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    result: &ResultAccount,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, std::io::Error> {
+    let (pool_pubkey, pool_bump_seed) = find_pool_address(program_id, result_pubkey);
+    let (pool_tokens_pubkey, pool_tokens_bump_seed) =
+        find_pool_tokens_address(program_id, &pool_pubkey);
+    let (lp_mint_pubkey, lp_mint_bump_seed) = find_lp_mint_address(program_id, &pool_pubkey);
+    let data = SearchMarketInstruction::CreatePool {
+        pool_bump_seed,
+        pool_tokens_bump_seed,
+        lp_mint_bump_seed,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(pool_pubkey, false),
+        AccountMeta::new_readonly(*market_pubkey, false),
+        AccountMeta::new_readonly(*result_pubkey, false),
+        AccountMeta::new_readonly(result.yes_mint, false),
+        AccountMeta::new(pool_tokens_pubkey, false),
+        AccountMeta::new(lp_mint_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an empty pool for a result of an open market along with its token account and LP mint.
+/// Anyone may create it; the first `add_liquidity` sets its price.
+pub fn create_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool_bump_seed: u8,
+    pool_tokens_bump_seed: u8,
+    lp_mint_bump_seed: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account_info = next_account_info(account_info_iter)?;
+    let market_account_info = next_account_info(account_info_iter)?;
+    let result_account_info = next_account_info(account_info_iter)?;
+    let yes_mint_account_info = next_account_info(account_info_iter)?;
+    let pool_tokens_account_info = next_account_info(account_info_iter)?;
+    let lp_mint_account_info = next_account_info(account_info_iter)?;
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let rent_account_info = next_account_info(account_info_iter)?;
+    let spl_token_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+
+    let config = ConfigAccount::load_unpaused(program_id, config_account_info)?;
+
+    if *result_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.yes_mint != *yes_mint_account_info.key {
+        return Err(SearchMarketError::MintMismatch.into());
+    }
+
+    let pool_seeds: &[&[u8]] = &[
+        b"pool",
+        &result_account_info.key.to_bytes(),
+        &[pool_bump_seed],
+    ];
+    if Pubkey::create_program_address(pool_seeds, program_id)? != *pool_account_info.key {
+        msg!("Pool address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    let pool_tokens_seeds: &[&[u8]] = &[
+        b"pool_tokens",
+        &pool_account_info.key.to_bytes(),
+        &[pool_tokens_bump_seed],
+    ];
+    if Pubkey::create_program_address(pool_tokens_seeds, program_id)?
+        != *pool_tokens_account_info.key
+    {
+        msg!("Pool token account address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+    let lp_mint_seeds: &[&[u8]] = &[
+        b"lp_mint",
+        &pool_account_info.key.to_bytes(),
+        &[lp_mint_bump_seed],
+    ];
+    if Pubkey::create_program_address(lp_mint_seeds, program_id)? != *lp_mint_account_info.key {
+        msg!("LP mint address invalid");
+        return Err(SearchMarketError::InvalidAccountAddress.into());
+    }
+
+    let pool = PoolAccount {
+        account_type: AccountType::Pool as u8,
+        account_version: 0,
+        search_market: result.search_market,
+        result: *result_account_info.key,
+        yes_mint: result.yes_mint,
+        lp_mint: *lp_mint_account_info.key,
+        pool_tokens: *pool_tokens_account_info.key,
+        yes_reserve: 0,
+        sol_reserve: 0,
+        fee_bps: config.params.pool_fee_bps,
+        bump_seed: pool_bump_seed,
+    };
+    pool.load_open_market(program_id, market_account_info)?;
+
+    if !payer_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *rent_account_info.key != rent::id() {
+        return Err(SearchMarketError::InvalidRentSysvar.into());
+    }
+    if *spl_token_account_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let data = pool.try_to_vec()?;
+    create_pda_account(
+        payer_account_info,
+        pool_account_info,
+        system_program_info,
+        data.len(),
+        program_id,
+        pool_seeds,
+    )?;
+    pool_account_info.data.borrow_mut().copy_from_slice(&data);
+
+    create_pda_account(
+        payer_account_info,
+        pool_tokens_account_info,
+        system_program_info,
+        Account::LEN,
+        &spl_token::id(),
+        pool_tokens_seeds,
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_account2(
+            &spl_token::id(),
+            pool_tokens_account_info.key,
+            yes_mint_account_info.key,
+            pool_account_info.key,
+        )?,
+        &[
+            pool_tokens_account_info.clone(),
+            yes_mint_account_info.clone(),
+            rent_account_info.clone(),
+            spl_token_account_info.clone(),
+        ],
+    )?;
+
+    create_pda_account(
+        payer_account_info,
+        lp_mint_account_info,
+        system_program_info,
+        Mint::LEN,
+        &spl_token::id(),
+        lp_mint_seeds,
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            lp_mint_account_info.key,
+            pool_account_info.key,
+            None,
+            spl_token::native_mint::DECIMALS,
+        )?,
+        &[
+            lp_mint_account_info.clone(),
+            rent_account_info.clone(),
+            spl_token_account_info.clone(),
+        ],
+    )?;
+
+    SearchMarketEvent::PoolCreated {
+        pool: *pool_account_info.key,
+        result: pool.result,
+        fee_bps: pool.fee_bps,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use solana_program_test::ProgramTest;
+
+    /// Creates `result`'s pool and an LP token account for `owner`, returning the pool's key, the
+    /// LP token account and the instructions that create them.
+    pub fn setup_pool(
+        market_key: &Pubkey,
+        result_key: &Pubkey,
+        result: &ResultAccount,
+        owner: &Pubkey,
+        program_test: &mut ProgramTest,
+        program_id: &Pubkey,
+    ) -> (Pubkey, Pubkey, Vec<Instruction>) {
+        let create_pool =
+            create_pool_instruction(program_id, market_key, result_key, result, owner).unwrap();
+        let (pool_key, _pool_bump_seed) = find_pool_address(program_id, result_key);
+        let (lp_mint_key, _lp_mint_bump_seed) = find_lp_mint_address(program_id, &pool_key);
+        let (lp_token, init_lp_token) = setup_token(&lp_mint_key, owner, program_test);
+        (pool_key, lp_token, vec![create_pool, init_lp_token])
+    }
+}
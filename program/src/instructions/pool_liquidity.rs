@@ -0,0 +1,549 @@
+use super::{
+    find_config_address, find_lp_mint_address, find_pool_address, find_pool_tokens_address,
+    transfer_program_lamports, ConfigAccount, PoolAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+use spl_token::state::Mint;
+use std::convert::TryFrom;
+
+/// `amount * numerator / denominator`, rounded up if `round_up`.
+fn pro_rata(amount: u64, numerator: u64, denominator: u64, round_up: bool) -> Option<u64> {
+    let product = (amount as u128).checked_mul(numerator as u128)?;
+    let rounding = if round_up { denominator as u128 - 1 } else { 0 };
+    u64::try_from((product + rounding).checked_div(denominator as u128)?).ok()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn add_liquidity_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    provider_pubkey: &Pubkey,
+    yes_token_pubkey: &Pubkey,
+    lp_token_pubkey: &Pubkey,
+    yes_amount: u64,
+    max_sol: u64,
+) -> Result<Instruction, std::io::Error> {
+    let (pool_pubkey, _pool_bump_seed) = find_pool_address(program_id, result_pubkey);
+    let data = SearchMarketInstruction::AddLiquidity {
+        yes_amount,
+        max_sol,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(pool_pubkey, false),
+        AccountMeta::new_readonly(*market_pubkey, false),
+        AccountMeta::new(find_pool_tokens_address(program_id, &pool_pubkey).0, false),
+        AccountMeta::new(find_lp_mint_address(program_id, &pool_pubkey).0, false),
+        AccountMeta::new(*provider_pubkey, true),
+        AccountMeta::new(*yes_token_pubkey, false),
+        AccountMeta::new(*lp_token_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Adds `yes_amount` yes tokens and lamports in the pool's current ratio, minting LP tokens for
+/// the same share of the pool. The first provider sets the price with `max_sol` and gets one LP
+/// token per lamport.
+pub fn add_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    yes_amount: u64,
+    max_sol: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account_info = next_account_info(account_info_iter)?;
+    let market_account_info = next_account_info(account_info_iter)?;
+    let pool_tokens_account_info = next_account_info(account_info_iter)?;
+    let lp_mint_account_info = next_account_info(account_info_iter)?;
+    let provider_account_info = next_account_info(account_info_iter)?;
+    let yes_token_account_info = next_account_info(account_info_iter)?;
+    let lp_token_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+
+    ConfigAccount::load_unpaused(program_id, config_account_info)?;
+    let mut pool = PoolAccount::load(
+        program_id,
+        pool_account_info,
+        pool_tokens_account_info,
+        Some(lp_mint_account_info),
+    )?;
+    pool.load_open_market(program_id, market_account_info)?;
+
+    if !provider_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let lp_supply = Mint::unpack(&lp_mint_account_info.data.borrow())?.supply;
+    let (sol_amount, lp_amount) = if lp_supply == 0 {
+        (max_sol, max_sol)
+    } else {
+        (
+            pro_rata(yes_amount, pool.sol_reserve, pool.yes_reserve, true)
+                .ok_or(SearchMarketError::ArithmeticOverflow)?,
+            pro_rata(yes_amount, lp_supply, pool.yes_reserve, false)
+                .ok_or(SearchMarketError::ArithmeticOverflow)?,
+        )
+    };
+    if sol_amount > max_sol {
+        msg!("Liquidity needs {} lamports, above {}", sol_amount, max_sol);
+        return Err(SearchMarketError::SlippageExceeded.into());
+    }
+    if yes_amount == 0 || sol_amount == 0 || lp_amount == 0 {
+        return Err(SearchMarketError::InvalidPoolAmount.into());
+    }
+
+    msg!("transfer tokens provider to pool");
+    invoke(
+        &spl_token::instruction::transfer(
+            spl_token_program_info.key,
+            yes_token_account_info.key,
+            pool_tokens_account_info.key,
+            provider_account_info.key,
+            &[],
+            yes_amount,
+        )?,
+        &[
+            yes_token_account_info.clone(),
+            pool_tokens_account_info.clone(),
+            provider_account_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+    )?;
+
+    msg!("transfer sol provider to pool");
+    invoke(
+        &system_instruction::transfer(provider_account_info.key, pool_account_info.key, sol_amount),
+        &[
+            provider_account_info.clone(),
+            pool_account_info.clone(),
+            system_program_info.clone(),
+        ],
+    )?;
+
+    msg!("mint lp tokens to provider");
+    let bump_seed = [pool.bump_seed];
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            spl_token_program_info.key,
+            lp_mint_account_info.key,
+            lp_token_account_info.key,
+            pool_account_info.key,
+            &[],
+            lp_amount,
+        )?,
+        &[
+            lp_mint_account_info.clone(),
+            lp_token_account_info.clone(),
+            pool_account_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+        &[&pool.seeds(&bump_seed)],
+    )?;
+
+    pool.yes_reserve = pool
+        .yes_reserve
+        .checked_add(yes_amount)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    pool.sol_reserve = pool
+        .sol_reserve
+        .checked_add(sol_amount)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    pool.serialize(&mut &mut pool_account_info.data.borrow_mut()[..])?;
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_pool_reserves(&pool, pool_account_info, pool_tokens_account_info)?;
+
+    SearchMarketEvent::LiquidityAdded {
+        pool: *pool_account_info.key,
+        provider: *provider_account_info.key,
+        yes_amount,
+        sol_amount,
+        lp_amount,
+    }
+    .emit()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn remove_liquidity_instruction(
+    program_id: &Pubkey,
+    result_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    lp_token_pubkey: &Pubkey,
+    yes_token_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    lp_amount: u64,
+    min_yes: u64,
+    min_sol: u64,
+) -> Result<Instruction, std::io::Error> {
+    let (pool_pubkey, _pool_bump_seed) = find_pool_address(program_id, result_pubkey);
+    let data = SearchMarketInstruction::RemoveLiquidity {
+        lp_amount,
+        min_yes,
+        min_sol,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(pool_pubkey, false),
+        AccountMeta::new(find_pool_tokens_address(program_id, &pool_pubkey).0, false),
+        AccountMeta::new(find_lp_mint_address(program_id, &pool_pubkey).0, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*lp_token_pubkey, false),
+        AccountMeta::new(*yes_token_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Burns `lp_amount` LP tokens for their share of both reserves. Works in any market state and
+/// while paused, so liquidity can always be taken out and its yes tokens redeemed.
+pub fn remove_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lp_amount: u64,
+    min_yes: u64,
+    min_sol: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account_info = next_account_info(account_info_iter)?;
+    let pool_tokens_account_info = next_account_info(account_info_iter)?;
+    let lp_mint_account_info = next_account_info(account_info_iter)?;
+    let owner_account_info = next_account_info(account_info_iter)?;
+    let lp_token_account_info = next_account_info(account_info_iter)?;
+    let yes_token_account_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+
+    let mut pool = PoolAccount::load(
+        program_id,
+        pool_account_info,
+        pool_tokens_account_info,
+        Some(lp_mint_account_info),
+    )?;
+    if !owner_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+
+    let lp_supply = Mint::unpack(&lp_mint_account_info.data.borrow())?.supply;
+    if lp_amount == 0 || lp_amount > lp_supply {
+        return Err(SearchMarketError::InvalidPoolAmount.into());
+    }
+    let yes_amount = pro_rata(lp_amount, pool.yes_reserve, lp_supply, false)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    let sol_amount = pro_rata(lp_amount, pool.sol_reserve, lp_supply, false)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if yes_amount < min_yes || sol_amount < min_sol {
+        msg!(
+            "Liquidity returns {} yes and {} lamports",
+            yes_amount,
+            sol_amount
+        );
+        return Err(SearchMarketError::SlippageExceeded.into());
+    }
+
+    msg!("burn lp tokens of owner");
+    invoke(
+        &spl_token::instruction::burn(
+            spl_token_program_info.key,
+            lp_token_account_info.key,
+            lp_mint_account_info.key,
+            owner_account_info.key,
+            &[],
+            lp_amount,
+        )?,
+        &[
+            lp_token_account_info.clone(),
+            lp_mint_account_info.clone(),
+            owner_account_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+    )?;
+
+    msg!("transfer tokens pool to owner");
+    let bump_seed = [pool.bump_seed];
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            spl_token_program_info.key,
+            pool_tokens_account_info.key,
+            yes_token_account_info.key,
+            pool_account_info.key,
+            &[],
+            yes_amount,
+        )?,
+        &[
+            pool_tokens_account_info.clone(),
+            yes_token_account_info.clone(),
+            pool_account_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+        &[&pool.seeds(&bump_seed)],
+    )?;
+    transfer_program_lamports(pool_account_info, destination_info, sol_amount)?;
+
+    pool.yes_reserve -= yes_amount;
+    pool.sol_reserve -= sol_amount;
+    pool.serialize(&mut &mut pool_account_info.data.borrow_mut()[..])?;
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_pool_reserves(&pool, pool_account_info, pool_tokens_account_info)?;
+
+    SearchMarketEvent::LiquidityRemoved {
+        pool: *pool_account_info.key,
+        owner: *owner_account_info.key,
+        yes_amount,
+        sol_amount,
+        lp_amount,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use crate::{ResultAccount, SearchMarketAccount};
+    use borsh::BorshDeserialize;
+    use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+    use spl_token::state::Account;
+
+    async fn process(
+        context: &mut ProgramTestContext,
+        instruction: Instruction,
+        signer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, signer], context.last_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    async fn token_amount(context: &mut ProgramTestContext, token: Pubkey) -> u64 {
+        let account = context
+            .banks_client
+            .get_account(token)
+            .await
+            .unwrap()
+            .unwrap();
+        Account::unpack(&account.data).unwrap().amount
+    }
+
+    async fn load_pool(context: &mut ProgramTestContext, pool_key: Pubkey) -> PoolAccount {
+        let account = context
+            .banks_client
+            .get_account(pool_key)
+            .await
+            .unwrap()
+            .unwrap();
+        PoolAccount::try_from_slice(&account.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pool_liquidity() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 10, &mut program_test, &program_id);
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let provider = Keypair::new();
+        program_test.add_account(
+            provider.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let (yes_token, init_yes_token) =
+            setup_token(&result.yes_mint, &provider.pubkey(), &mut program_test);
+        let (no_token, init_no_token) =
+            setup_token(&result.no_mint, &provider.pubkey(), &mut program_test);
+        let depositor = Keypair::new();
+        let deposit = setup_deposit(
+            &depositor.pubkey(),
+            100,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token,
+            &no_token,
+            &mut program_test,
+            &program_id,
+        );
+        let (pool_key, lp_token, create_pool) = setup_pool(
+            &market_key,
+            &result_key,
+            &result,
+            &provider.pubkey(),
+            &mut program_test,
+            &program_id,
+        );
+
+        let mut context = program_test.start_with_context().await;
+        let mut instructions = vec![
+            create_market,
+            create_result,
+            init_yes_token,
+            init_no_token,
+            deposit,
+        ];
+        instructions.extend(create_pool);
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &decision_authority, &depositor, &provider],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let pool = load_pool(&mut context, pool_key).await;
+        assert_eq!(pool.fee_bps, test_config_params().pool_fee_bps);
+        assert_eq!(pool.yes_price(), None);
+
+        let add = |yes_amount, max_sol| {
+            add_liquidity_instruction(
+                &program_id,
+                &market_key,
+                &result_key,
+                &provider.pubkey(),
+                &yes_token,
+                &lp_token,
+                yes_amount,
+                max_sol,
+            )
+            .unwrap()
+        };
+        process(&mut context, add(50, 2_500_000), &provider)
+            .await
+            .unwrap();
+        let pool = load_pool(&mut context, pool_key).await;
+        assert_eq!((pool.yes_reserve, pool.sol_reserve), (50, 2_500_000));
+        assert_eq!(pool.yes_price(), Some(50_000));
+        assert_eq!(token_amount(&mut context, lp_token).await, 2_500_000);
+
+        assert_eq!(
+            process(&mut context, add(10, 499_999), &provider)
+                .await
+                .unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::SlippageExceeded as u32)
+            )
+        );
+        process(&mut context, add(10, 500_000), &provider)
+            .await
+            .unwrap();
+        let pool = load_pool(&mut context, pool_key).await;
+        assert_eq!((pool.yes_reserve, pool.sol_reserve), (60, 3_000_000));
+        assert_eq!(token_amount(&mut context, lp_token).await, 3_000_000);
+        assert_eq!(token_amount(&mut context, yes_token).await, 40);
+
+        let remove = |min_yes| {
+            remove_liquidity_instruction(
+                &program_id,
+                &result_key,
+                &provider.pubkey(),
+                &lp_token,
+                &yes_token,
+                &provider.pubkey(),
+                3_000_000,
+                min_yes,
+                3_000_000,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            process(&mut context, remove(61), &provider)
+                .await
+                .unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::SlippageExceeded as u32)
+            )
+        );
+        let provider_before = context
+            .banks_client
+            .get_balance(provider.pubkey())
+            .await
+            .unwrap();
+        process(&mut context, remove(60), &provider).await.unwrap();
+        let provider_after = context
+            .banks_client
+            .get_balance(provider.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(provider_after, provider_before + 3_000_000);
+        let pool = load_pool(&mut context, pool_key).await;
+        assert_eq!((pool.yes_reserve, pool.sol_reserve), (0, 0));
+        assert_eq!(token_amount(&mut context, lp_token).await, 0);
+        assert_eq!(token_amount(&mut context, yes_token).await, 100);
+    }
+}
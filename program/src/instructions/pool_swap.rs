@@ -0,0 +1,424 @@
+use super::{
+    fee_amount, find_config_address, find_pool_address, find_pool_tokens_address,
+    transfer_program_lamports, ConfigAccount, PoolAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+use std::convert::TryFrom;
+
+/// `buy_yes` swaps `amount_in` lamports for yes tokens, otherwise `amount_in` yes tokens for
+/// lamports.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    trader_pubkey: &Pubkey,
+    yes_token_pubkey: &Pubkey,
+    buy_yes: bool,
+    amount_in: u64,
+    min_out: u64,
+) -> Result<Instruction, std::io::Error> {
+    let (pool_pubkey, _pool_bump_seed) = find_pool_address(program_id, result_pubkey);
+    let data = SearchMarketInstruction::Swap {
+        buy_yes,
+        amount_in,
+        min_out,
+    }
+    .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(pool_pubkey, false),
+        AccountMeta::new_readonly(*market_pubkey, false),
+        AccountMeta::new(find_pool_tokens_address(program_id, &pool_pubkey).0, false),
+        AccountMeta::new(*trader_pubkey, true),
+        AccountMeta::new(*yes_token_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// What the pool pays out for `amount_in` once the fee is taken, keeping the product of its
+/// reserves from falling.
+pub fn swap_output(
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+    fee_bps: u16,
+) -> Result<u64, SearchMarketError> {
+    let amount_in = amount_in - fee_amount(amount_in, fee_bps)?;
+    let numerator = reserve_out as u128 * amount_in as u128;
+    let denominator = reserve_in as u128 + amount_in as u128;
+    numerator
+        .checked_div(denominator)
+        .and_then(|output| u64::try_from(output).ok())
+        .ok_or(SearchMarketError::ArithmeticOverflow)
+}
+
+/// Swaps against the pool at its constant-product price. The whole input, fee included, joins the
+/// reserves, so the fee accrues to liquidity providers.
+pub fn swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    buy_yes: bool,
+    amount_in: u64,
+    min_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account_info = next_account_info(account_info_iter)?;
+    let market_account_info = next_account_info(account_info_iter)?;
+    let pool_tokens_account_info = next_account_info(account_info_iter)?;
+    let trader_account_info = next_account_info(account_info_iter)?;
+    let yes_token_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let config_account_info = next_account_info(account_info_iter)?;
+
+    ConfigAccount::load_unpaused(program_id, config_account_info)?;
+    let mut pool = PoolAccount::load(
+        program_id,
+        pool_account_info,
+        pool_tokens_account_info,
+        None,
+    )?;
+    pool.load_open_market(program_id, market_account_info)?;
+
+    if !trader_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    let (reserve_in, reserve_out) = if buy_yes {
+        (pool.sol_reserve, pool.yes_reserve)
+    } else {
+        (pool.yes_reserve, pool.sol_reserve)
+    };
+    let amount_out = swap_output(reserve_in, reserve_out, amount_in, pool.fee_bps)?;
+    if amount_out < min_out {
+        msg!("Swap returns {}, below {}", amount_out, min_out);
+        return Err(SearchMarketError::SlippageExceeded.into());
+    }
+    if amount_out == 0 {
+        return Err(SearchMarketError::InvalidPoolAmount.into());
+    }
+
+    let bump_seed = [pool.bump_seed];
+    if buy_yes {
+        msg!("transfer sol trader to pool");
+        invoke(
+            &system_instruction::transfer(
+                trader_account_info.key,
+                pool_account_info.key,
+                amount_in,
+            ),
+            &[
+                trader_account_info.clone(),
+                pool_account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+
+        msg!("transfer tokens pool to trader");
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                spl_token_program_info.key,
+                pool_tokens_account_info.key,
+                yes_token_account_info.key,
+                pool_account_info.key,
+                &[],
+                amount_out,
+            )?,
+            &[
+                pool_tokens_account_info.clone(),
+                yes_token_account_info.clone(),
+                pool_account_info.clone(),
+                spl_token_program_info.clone(),
+            ],
+            &[&pool.seeds(&bump_seed)],
+        )?;
+    } else {
+        msg!("transfer tokens trader to pool");
+        invoke(
+            &spl_token::instruction::transfer(
+                spl_token_program_info.key,
+                yes_token_account_info.key,
+                pool_tokens_account_info.key,
+                trader_account_info.key,
+                &[],
+                amount_in,
+            )?,
+            &[
+                yes_token_account_info.clone(),
+                pool_tokens_account_info.clone(),
+                trader_account_info.clone(),
+                spl_token_program_info.clone(),
+            ],
+        )?;
+        transfer_program_lamports(pool_account_info, trader_account_info, amount_out)?;
+    }
+
+    let reserve_in = reserve_in
+        .checked_add(amount_in)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    let reserve_out = reserve_out - amount_out;
+    if buy_yes {
+        pool.sol_reserve = reserve_in;
+        pool.yes_reserve = reserve_out;
+    } else {
+        pool.yes_reserve = reserve_in;
+        pool.sol_reserve = reserve_out;
+    }
+    if let Some(price) = pool.yes_price() {
+        msg!("Pool yes price {}", price);
+    }
+    pool.serialize(&mut &mut pool_account_info.data.borrow_mut()[..])?;
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_pool_reserves(&pool, pool_account_info, pool_tokens_account_info)?;
+
+    SearchMarketEvent::Swapped {
+        pool: *pool_account_info.key,
+        trader: *trader_account_info.key,
+        buy_yes,
+        amount_in,
+        amount_out,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use crate::{add_liquidity_instruction, ResultAccount, SearchMarketAccount};
+    use borsh::BorshDeserialize;
+    use solana_program::program_pack::Pack;
+    use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+    use spl_token::state::Account;
+
+    async fn process(
+        context: &mut ProgramTestContext,
+        instruction: Instruction,
+        signer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer, signer], context.last_blockhash);
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    async fn load_pool(context: &mut ProgramTestContext, pool_key: Pubkey) -> PoolAccount {
+        let account = context
+            .banks_client
+            .get_account(pool_key)
+            .await
+            .unwrap()
+            .unwrap();
+        PoolAccount::try_from_slice(&account.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pool_swap() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 10, &mut program_test, &program_id);
+        let mut result = ResultAccount::new(
+            market_key,
+            String::from("http://cyberpunk.net"),
+            String::from("Cyberpunk website"),
+            String::from("A game fated to be legend"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (result_key, create_result) =
+            setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+        let provider = Keypair::new();
+        program_test.add_account(
+            provider.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let (yes_token, init_yes_token) =
+            setup_token(&result.yes_mint, &provider.pubkey(), &mut program_test);
+        let (no_token, init_no_token) =
+            setup_token(&result.no_mint, &provider.pubkey(), &mut program_test);
+        let depositor = Keypair::new();
+        let deposit = setup_deposit(
+            &depositor.pubkey(),
+            50,
+            &market_key,
+            &result_key,
+            &result,
+            &yes_token,
+            &no_token,
+            &mut program_test,
+            &program_id,
+        );
+        let (pool_key, lp_token, create_pool) = setup_pool(
+            &market_key,
+            &result_key,
+            &result,
+            &provider.pubkey(),
+            &mut program_test,
+            &program_id,
+        );
+        let add_liquidity = add_liquidity_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &provider.pubkey(),
+            &yes_token,
+            &lp_token,
+            50,
+            2_500_000,
+        )
+        .unwrap();
+
+        let trader = Keypair::new();
+        program_test.add_account(
+            trader.pubkey(),
+            SolanaAccount::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        );
+        let (trader_yes_token, init_trader_yes_token) =
+            setup_token(&result.yes_mint, &trader.pubkey(), &mut program_test);
+
+        let mut context = program_test.start_with_context().await;
+        let mut instructions = vec![
+            create_market,
+            create_result,
+            init_yes_token,
+            init_no_token,
+            deposit,
+            init_trader_yes_token,
+        ];
+        instructions.extend(create_pool);
+        instructions.push(add_liquidity);
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &decision_authority, &depositor, &provider],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let fee_bps = test_config_params().pool_fee_bps;
+        let bought = swap_output(2_500_000, 50, 1_000_000, fee_bps).unwrap();
+        assert_eq!(bought, 14);
+        let buy = |min_out| {
+            swap_instruction(
+                &program_id,
+                &market_key,
+                &result_key,
+                &trader.pubkey(),
+                &trader_yes_token,
+                true,
+                1_000_000,
+                min_out,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            process(&mut context, buy(bought + 1), &trader)
+                .await
+                .unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::SlippageExceeded as u32)
+            )
+        );
+        process(&mut context, buy(bought), &trader).await.unwrap();
+
+        let pool = load_pool(&mut context, pool_key).await;
+        assert_eq!((pool.yes_reserve, pool.sol_reserve), (36, 3_500_000));
+        assert_eq!(pool.yes_price(), Some(3_500_000 / 36));
+        let trader_yes = context
+            .banks_client
+            .get_account(trader_yes_token)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Account::unpack(&trader_yes.data).unwrap().amount, bought);
+
+        let proceeds = swap_output(36, 3_500_000, bought, fee_bps).unwrap();
+        assert!(proceeds < 1_000_000);
+        let trader_before = context
+            .banks_client
+            .get_balance(trader.pubkey())
+            .await
+            .unwrap();
+        let sell = swap_instruction(
+            &program_id,
+            &market_key,
+            &result_key,
+            &trader.pubkey(),
+            &trader_yes_token,
+            false,
+            bought,
+            proceeds,
+        )
+        .unwrap();
+        process(&mut context, sell, &trader).await.unwrap();
+        let trader_after = context
+            .banks_client
+            .get_balance(trader.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(trader_after, trader_before + proceeds);
+
+        let pool = load_pool(&mut context, pool_key).await;
+        assert_eq!(
+            (pool.yes_reserve, pool.sol_reserve),
+            (50, 3_500_000 - proceeds)
+        );
+        assert!(pool.sol_reserve > 2_500_000);
+    }
+}
@@ -0,0 +1,851 @@
+use super::{
+    find_vault_address, ResultAccount, SearchMarketAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::SearchMarketError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction::transfer,
+    system_program,
+};
+
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    result_pubkey: &Pubkey,
+    redeem_pubkey: &Pubkey,
+    token_owner_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    token_pubkey: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, std::io::Error> {
+    let (vault_key, _bump_seed) = find_vault_address(program_id, market_pubkey);
+    let data = SearchMarketInstruction::Redeem { amount }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*market_pubkey, false),
+        AccountMeta::new(*result_pubkey, false),
+        AccountMeta::new(*redeem_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(vault_key, false),
+        AccountMeta::new_readonly(*token_owner_pubkey, true),
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new(*token_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Burns `amount` yes or no tokens of a finalized or voided market and pays out what they are
+/// worth. After `decide` the decided result's yes tokens win and every other result's no tokens
+/// win, after `decide_weighted` both sides of a result split the collateral by its weight, and if
+/// no result was decided no tokens win everywhere. Once the result's collateral runs out the
+/// market's pooled collateral makes up the rest.
+pub fn redeem(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let market_account_info = next_account_info(account_info_iter)?;
+    let result_account_info = next_account_info(account_info_iter)?;
+    let redeem_account_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let token_owner_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(*market_account_info.data.borrow())?;
+
+    if *result_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.search_market != *market_account_info.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
+    }
+
+    if !redeem_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if !market.is_settled() {
+        return Err(SearchMarketError::MarketNotFinalized.into());
+    }
+
+    if *system_program_info.key != system_program::id() {
+        return Err(SearchMarketError::InvalidSystemProgram.into());
+    }
+
+    if *spl_token_program_info.key != spl_token::id() {
+        return Err(SearchMarketError::InvalidTokenProgram.into());
+    }
+
+    let vault_seeds: &[&[u8]] = &[
+        b"vault",
+        &market_account_info.key.to_bytes(),
+        &[market.vault_bump_seed],
+    ];
+    if Pubkey::create_program_address(vault_seeds, program_id)? != *vault_account_info.key {
+        return Err(SearchMarketError::InvalidVault.into());
+    }
+
+    let (yes_value, no_value) = market.redemption_values(result_account_info.key, &result);
+    let token_value = if *mint_account_info.key == result.yes_mint {
+        yes_value
+    } else if *mint_account_info.key == result.no_mint {
+        no_value
+    } else {
+        0
+    };
+    if token_value == 0 {
+        msg!(
+            "Tokens of mint {} redeem for nothing",
+            mint_account_info.key
+        );
+        return Err(SearchMarketError::MintMismatch.into());
+    }
+
+    let redeem_amount = amount
+        .checked_mul(token_value)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    let shortfall = redeem_amount.saturating_sub(result.collateral);
+    if shortfall > market.pooled_collateral {
+        msg!(
+            "Redeem {} exceeds result collateral {} and pool {}",
+            redeem_amount,
+            result.collateral,
+            market.pooled_collateral
+        );
+        return Err(SearchMarketError::InsufficientCollateral.into());
+    }
+
+    msg!("transfer sol vault to redeemer");
+    invoke_signed(
+        &transfer(
+            vault_account_info.key,
+            redeem_account_info.key,
+            redeem_amount,
+        ),
+        &[
+            vault_account_info.clone(),
+            redeem_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[vault_seeds],
+    )?;
+
+    msg!("burn winning tokens");
+    invoke(
+        &spl_token::instruction::burn(
+            spl_token_program_info.key,
+            token_account_info.key,
+            mint_account_info.key,
+            token_owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            token_account_info.clone(),
+            mint_account_info.clone(),
+            token_owner_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+    )?;
+
+    result.collateral -= redeem_amount - shortfall;
+    result.serialize(&mut &mut result_account_info.data.borrow_mut()[..])?;
+    if shortfall > 0 {
+        market.pooled_collateral -= shortfall;
+        market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+    }
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_settled_backing(
+        &market,
+        &result,
+        vault_account_info,
+        mint_account_info,
+        token_value,
+    )?;
+
+    SearchMarketEvent::Redeemed {
+        market: *market_account_info.key,
+        result: *result_account_info.key,
+        owner: *token_owner_info.key,
+        mint: *mint_account_info.key,
+        amount,
+        lamports: redeem_amount,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::instructions::{
+        decide_instruction, decide_ranked_instruction, decide_weighted_instruction,
+        finalize_market_instruction, void_market_instruction,
+    };
+    use crate::process_instruction;
+    use crate::LAMPORTS_PER_TOKEN;
+    use solana_program::program_pack::Pack;
+    use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+    use solana_sdk::{
+        account::Account as SolanaAccount,
+        instruction::InstructionError,
+        rent::Rent,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+    use spl_token::state::Account;
+
+    struct RedeemTest {
+        program_id: Pubkey,
+        decision_authority: Keypair,
+        market_key: Pubkey,
+        market: SearchMarketAccount,
+        result_key: Pubkey,
+        result: ResultAccount,
+        yes_token_pubkey: Pubkey,
+        no_token_pubkey: Pubkey,
+        deposit_keypair: Keypair,
+        redeem_keypair: Keypair,
+        instructions: Vec<Instruction>,
+    }
+
+    impl RedeemTest {
+        fn new() -> (RedeemTest, ProgramTest) {
+            RedeemTest::with_rank_schedule(vec![])
+        }
+
+        fn with_rank_schedule(rank_schedule: Vec<u16>) -> (RedeemTest, ProgramTest) {
+            let program_id = crate::id();
+            let mut program_test =
+                ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+            let decision_authority = Keypair::new();
+            let mut market = SearchMarketAccount::new(
+                decision_authority.pubkey(),
+                "cyberpunk".to_string(),
+                2,
+                10,
+                0,
+            );
+            market.fee_destination = Pubkey::new_unique();
+            market.rank_schedule = rank_schedule;
+            let (market_key, create_market) =
+                setup_market(&mut market, 1, &mut program_test, &program_id);
+
+            let mut result = ResultAccount::new(
+                market_key,
+                String::from("http://cyberpunk.net"),
+                String::from("Cyberpunk website"),
+                String::from("A game fated to be legend"),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            );
+            let (result_key, create_result) =
+                setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
+
+            let deposit_keypair = Keypair::new();
+            let (yes_token_pubkey, init_yes_token) = setup_token(
+                &result.yes_mint,
+                &deposit_keypair.pubkey(),
+                &mut program_test,
+            );
+            let (no_token_pubkey, init_no_token) = setup_token(
+                &result.no_mint,
+                &deposit_keypair.pubkey(),
+                &mut program_test,
+            );
+            let deposit_instruction = setup_deposit(
+                &deposit_keypair.pubkey(),
+                100,
+                &market_key,
+                &result_key,
+                &result,
+                &yes_token_pubkey,
+                &no_token_pubkey,
+                &mut program_test,
+                &program_id,
+            );
+
+            let redeem_keypair = Keypair::new();
+            program_test.add_account(
+                redeem_keypair.pubkey(),
+                SolanaAccount::new(Rent::default().minimum_balance(0), 0, &system_program::id()),
+            );
+
+            let redeem_test = RedeemTest {
+                program_id,
+                decision_authority,
+                market_key,
+                market,
+                result_key,
+                result,
+                yes_token_pubkey,
+                no_token_pubkey,
+                deposit_keypair,
+                redeem_keypair,
+                instructions: vec![
+                    create_market,
+                    create_result,
+                    init_yes_token,
+                    init_no_token,
+                    deposit_instruction,
+                ],
+            };
+            (redeem_test, program_test)
+        }
+
+        fn redeem(&self, mint: &Pubkey, token: &Pubkey, amount: u64) -> Instruction {
+            redeem_instruction(
+                &self.program_id,
+                &self.market_key,
+                &self.result_key,
+                &self.redeem_keypair.pubkey(),
+                &self.deposit_keypair.pubkey(),
+                mint,
+                token,
+                amount,
+            )
+            .unwrap()
+        }
+
+        async fn finalize(&self, context: &mut ProgramTestContext) {
+            let finalize =
+                finalize_market_instruction(&self.program_id, &self.market_key, None).unwrap();
+            let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+            let mut transaction =
+                Transaction::new_with_payer(&[finalize], Some(&context.payer.pubkey()));
+            transaction.sign(&[&context.payer], recent_blockhash);
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap();
+        }
+
+        async fn process(
+            &self,
+            context: &mut ProgramTestContext,
+            instruction: Instruction,
+        ) -> Result<(), TransactionError> {
+            let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+            transaction.sign(
+                &[&context.payer, &self.deposit_keypair, &self.redeem_keypair],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|e| e.unwrap())
+        }
+    }
+
+    async fn token_amount(context: &mut ProgramTestContext, token: Pubkey) -> u64 {
+        let token_account = context
+            .banks_client
+            .get_account(token)
+            .await
+            .unwrap()
+            .unwrap();
+        Account::unpack_from_slice(&token_account.data)
+            .unwrap()
+            .amount
+    }
+
+    #[tokio::test]
+    async fn test_redeem_decided() {
+        let (redeem_test, program_test) = RedeemTest::new();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&redeem_test.instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[
+                &context.payer,
+                &redeem_test.decision_authority,
+                &redeem_test.deposit_keypair,
+            ],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let yes_mint = redeem_test.result.yes_mint;
+        let no_mint = redeem_test.result.no_mint;
+        let error = redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&yes_mint, &redeem_test.yes_token_pubkey, 40),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::MarketNotFinalized as u32)
+            )
+        );
+
+        context.warp_to_slot(3).unwrap();
+        let decide = decide_instruction(
+            &redeem_test.program_id,
+            &redeem_test.market_key,
+            &redeem_test.decision_authority.pubkey(),
+            &redeem_test.result_key,
+            &redeem_test.market.fee_destination,
+        )
+        .unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+        let mut transaction = Transaction::new_with_payer(&[decide], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &redeem_test.decision_authority],
+            recent_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(6).unwrap();
+        redeem_test.finalize(&mut context).await;
+
+        let error = redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&no_mint, &redeem_test.no_token_pubkey, 40),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::MintMismatch as u32)
+            )
+        );
+
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&yes_mint, &redeem_test.yes_token_pubkey, 40),
+            )
+            .await
+            .unwrap();
+
+        let redeem_account = context
+            .banks_client
+            .get_account(redeem_test.redeem_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            redeem_account.lamports,
+            Rent::default().minimum_balance(0) + 40 * LAMPORTS_PER_TOKEN
+        );
+        assert_eq!(
+            token_amount(&mut context, redeem_test.yes_token_pubkey).await,
+            60
+        );
+        assert_eq!(
+            token_amount(&mut context, redeem_test.no_token_pubkey).await,
+            100
+        );
+
+        let result_account = context
+            .banks_client
+            .get_account(redeem_test.result_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let result = ResultAccount::try_from_slice(&result_account.data).unwrap();
+        assert_eq!(result.collateral, 60 * LAMPORTS_PER_TOKEN);
+    }
+
+    #[tokio::test]
+    async fn test_redeem_undecided() {
+        let (redeem_test, program_test) = RedeemTest::new();
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&redeem_test.instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[
+                &context.payer,
+                &redeem_test.decision_authority,
+                &redeem_test.deposit_keypair,
+            ],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(5).unwrap();
+        redeem_test.finalize(&mut context).await;
+
+        let no_mint = redeem_test.result.no_mint;
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&no_mint, &redeem_test.no_token_pubkey, 100),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_amount(&mut context, redeem_test.no_token_pubkey).await,
+            0
+        );
+        let redeem_account = context
+            .banks_client
+            .get_account(redeem_test.redeem_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            redeem_account.lamports,
+            Rent::default().minimum_balance(0) + 100 * LAMPORTS_PER_TOKEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redeem_weighted() {
+        let (mut redeem_test, program_test) = RedeemTest::new();
+        let mut other_result = ResultAccount::new(
+            redeem_test.market_key,
+            String::from("http://cyberpunk2077.com"),
+            String::from("Cyberpunk 2077"),
+            String::from("Official site"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (other_result_key, create_other_result) = setup_result(
+            &mut other_result,
+            1,
+            &redeem_test.decision_authority.pubkey(),
+            &redeem_test.program_id,
+        );
+        redeem_test.instructions.push(create_other_result);
+
+        let decide_weighted = |weights: &[(Pubkey, u16)]| {
+            decide_weighted_instruction(
+                &redeem_test.program_id,
+                &redeem_test.market_key,
+                &redeem_test.decision_authority.pubkey(),
+                &[redeem_test.decision_authority.pubkey()],
+                &redeem_test.market.fee_destination,
+                weights,
+            )
+            .unwrap()
+        };
+        let overweight =
+            decide_weighted(&[(redeem_test.result_key, 7_000), (other_result_key, 7_000)]);
+        let weighted =
+            decide_weighted(&[(redeem_test.result_key, 7_000), (other_result_key, 3_000)]);
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&redeem_test.instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[
+                &context.payer,
+                &redeem_test.decision_authority,
+                &redeem_test.deposit_keypair,
+            ],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[overweight], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &redeem_test.decision_authority],
+            recent_blockhash,
+        );
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidPayoutWeights as u32)
+            )
+        );
+
+        let mut transaction =
+            Transaction::new_with_payer(&[weighted], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &redeem_test.decision_authority],
+            recent_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(6).unwrap();
+        redeem_test.finalize(&mut context).await;
+
+        let yes_mint = redeem_test.result.yes_mint;
+        let no_mint = redeem_test.result.no_mint;
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&yes_mint, &redeem_test.yes_token_pubkey, 10),
+            )
+            .await
+            .unwrap();
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&no_mint, &redeem_test.no_token_pubkey, 10),
+            )
+            .await
+            .unwrap();
+
+        let redeem_account = context
+            .banks_client
+            .get_account(redeem_test.redeem_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            redeem_account.lamports,
+            Rent::default().minimum_balance(0) + 10 * 70_000 + 10 * 30_000
+        );
+
+        let result_account = context
+            .banks_client
+            .get_account(redeem_test.result_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let result = ResultAccount::try_from_slice(&result_account.data).unwrap();
+        assert_eq!(result.payout_bps, 7_000);
+        assert_eq!(result.collateral, 90 * LAMPORTS_PER_TOKEN);
+    }
+
+    #[tokio::test]
+    async fn test_redeem_ranked() {
+        let (mut redeem_test, program_test) =
+            RedeemTest::with_rank_schedule(vec![10_000, 5_000, 2_500]);
+        let mut top_result = ResultAccount::new(
+            redeem_test.market_key,
+            String::from("http://cyberpunk2077.com"),
+            String::from("Cyberpunk 2077"),
+            String::from("Official site"),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (top_result_key, create_top_result) = setup_result(
+            &mut top_result,
+            1,
+            &redeem_test.decision_authority.pubkey(),
+            &redeem_test.program_id,
+        );
+        redeem_test.instructions.push(create_top_result);
+
+        let decide_ranked = |ranking: &[Pubkey]| {
+            decide_ranked_instruction(
+                &redeem_test.program_id,
+                &redeem_test.market_key,
+                &redeem_test.decision_authority.pubkey(),
+                &[redeem_test.decision_authority.pubkey()],
+                &redeem_test.market.fee_destination,
+                ranking,
+            )
+            .unwrap()
+        };
+        let repeated = decide_ranked(&[top_result_key, top_result_key]);
+        let ranked = decide_ranked(&[top_result_key, redeem_test.result_key]);
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&redeem_test.instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[
+                &context.payer,
+                &redeem_test.decision_authority,
+                &redeem_test.deposit_keypair,
+            ],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(3).unwrap();
+        let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[repeated], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &redeem_test.decision_authority],
+            recent_blockhash,
+        );
+        let error = context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidRanking as u32)
+            )
+        );
+
+        let mut transaction = Transaction::new_with_payer(&[ranked], Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[&context.payer, &redeem_test.decision_authority],
+            recent_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        context.warp_to_slot(6).unwrap();
+        redeem_test.finalize(&mut context).await;
+
+        let market_account = context
+            .banks_client
+            .get_account(redeem_test.market_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let market = SearchMarketAccount::try_from_slice(&market_account.data).unwrap();
+        assert_eq!(market.best_result, top_result_key);
+        assert_eq!(
+            market.ranking,
+            vec![top_result_key, redeem_test.result_key, Pubkey::default()]
+        );
+
+        let yes_mint = redeem_test.result.yes_mint;
+        let no_mint = redeem_test.result.no_mint;
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&yes_mint, &redeem_test.yes_token_pubkey, 10),
+            )
+            .await
+            .unwrap();
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&no_mint, &redeem_test.no_token_pubkey, 20),
+            )
+            .await
+            .unwrap();
+
+        let redeem_account = context
+            .banks_client
+            .get_account(redeem_test.redeem_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            redeem_account.lamports,
+            Rent::default().minimum_balance(0) + 30 * 50_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redeem_voided() {
+        let (mut redeem_test, program_test) = RedeemTest::new();
+        let void_market = void_market_instruction(
+            &redeem_test.program_id,
+            &redeem_test.market_key,
+            &[redeem_test.decision_authority.pubkey()],
+        )
+        .unwrap();
+        redeem_test.instructions.push(void_market);
+
+        let mut context = program_test.start_with_context().await;
+        let mut transaction =
+            Transaction::new_with_payer(&redeem_test.instructions, Some(&context.payer.pubkey()));
+        transaction.sign(
+            &[
+                &context.payer,
+                &redeem_test.decision_authority,
+                &redeem_test.deposit_keypair,
+            ],
+            context.last_blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let yes_mint = redeem_test.result.yes_mint;
+        let no_mint = redeem_test.result.no_mint;
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&yes_mint, &redeem_test.yes_token_pubkey, 10),
+            )
+            .await
+            .unwrap();
+        redeem_test
+            .process(
+                &mut context,
+                redeem_test.redeem(&no_mint, &redeem_test.no_token_pubkey, 10),
+            )
+            .await
+            .unwrap();
+
+        let redeem_account = context
+            .banks_client
+            .get_account(redeem_test.redeem_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            redeem_account.lamports,
+            Rent::default().minimum_balance(0) + 10 * LAMPORTS_PER_TOKEN
+        );
+    }
+}
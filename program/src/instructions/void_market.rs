@@ -0,0 +1,172 @@
+use super::{
+    find_config_address, ConfigAccount, MarketState, SearchMarketAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::{SearchMarketError, BASIS_POINTS};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub fn void_market_instruction(
+    program_id: &Pubkey,
+    market_pubkey: &Pubkey,
+    signers: &[Pubkey],
+) -> Result<Instruction, std::io::Error> {
+    let data = SearchMarketInstruction::VoidMarket.try_to_vec()?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*market_pubkey, false),
+    ];
+    for signer in signers {
+        accounts.push(AccountMeta::new_readonly(*signer, true));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Cancels the outcome of a market that cannot be answered. The config admin or the decision
+/// authority may void until the market is finalized, unless a challenge awaits the arbiter. Every
+/// token then redeems at the config's void split, and resting orders can still be cancelled. Once
+/// sets have been converted the yes share is capped so the results' yes tokens together redeem for
+/// at most one token.
+pub fn void_market(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_account_info = next_account_info(account_info_iter)?;
+    let market_account_info = next_account_info(account_info_iter)?;
+    let clock = Clock::get()?;
+
+    let config = ConfigAccount::load(program_id, config_account_info)?;
+
+    if *market_account_info.owner != *program_id {
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut market = SearchMarketAccount::try_from_slice(&market_account_info.data.borrow())?;
+
+    let signers: Vec<Pubkey> = account_info_iter
+        .filter(|account| account.is_signer)
+        .map(|account| *account.key)
+        .collect();
+    if !signers.contains(&config.admin) && !market.approved_by(&signers) {
+        return Err(SearchMarketError::WrongDecisionAuthority.into());
+    }
+
+    if market.is_settled() || market.challenger != Pubkey::default() {
+        msg!("Cannot void in state {}", market.state);
+        return Err(SearchMarketError::InvalidMarketState.into());
+    }
+
+    market.state = MarketState::Voided as u8;
+    market.void_yes_bps = config.params.void_yes_bps;
+    if market.pooled_collateral > 0 {
+        // Converted sets are backed by one token's collateral across all results.
+        let exclusive_bps = BASIS_POINTS / market.result_count.max(1);
+        market.void_yes_bps = market.void_yes_bps.min(exclusive_bps as u16);
+    }
+    market.settled_slot = clock.slot;
+
+    market.serialize(&mut &mut market_account_info.data.borrow_mut()[..])?;
+
+    SearchMarketEvent::Voided {
+        market: *market_account_info.key,
+        void_yes_bps: market.void_yes_bps,
+    }
+    .emit()
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-bpf")]
+pub mod test {
+    use super::*;
+    use crate::instructions::test_utils::*;
+    use crate::process_instruction;
+    use solana_program_test::{processor, ProgramTest};
+    use solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+
+    #[tokio::test]
+    async fn test_void_market() {
+        let program_id = crate::id();
+        let mut program_test =
+            ProgramTest::new("askbid", program_id, processor!(process_instruction));
+
+        let decision_authority = Keypair::new();
+        let mut market = SearchMarketAccount::new(
+            decision_authority.pubkey(),
+            "cyberpunk".to_string(),
+            2,
+            10,
+            0,
+        );
+        let (market_key, create_market) =
+            setup_market(&mut market, 1, &mut program_test, &program_id);
+        let admin = Keypair::new();
+        let mut params = test_config_params();
+        params.void_yes_bps = 4_000;
+        setup_config_with_params(&mut program_test, &program_id, &admin.pubkey(), params);
+
+        let outsider = Keypair::new();
+        let outsider_void =
+            void_market_instruction(&program_id, &market_key, &[outsider.pubkey()]).unwrap();
+        let admin_void =
+            void_market_instruction(&program_id, &market_key, &[admin.pubkey()]).unwrap();
+        let authority_void =
+            void_market_instruction(&program_id, &market_key, &[decision_authority.pubkey()])
+                .unwrap();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let mut transaction = Transaction::new_with_payer(&[create_market], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &decision_authority], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let mut transaction = Transaction::new_with_payer(&[outsider_void], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &outsider], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::WrongDecisionAuthority as u32)
+            )
+        );
+
+        let mut transaction = Transaction::new_with_payer(&[admin_void], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let mut transaction = Transaction::new_with_payer(&[authority_void], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &decision_authority], recent_blockhash);
+        let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(SearchMarketError::InvalidMarketState as u32)
+            )
+        );
+
+        let market_account = banks_client.get_account(market_key).await.unwrap().unwrap();
+        let processed_market =
+            SearchMarketAccount::try_from_slice(&market_account.data[..]).unwrap();
+        assert_eq!(processed_market.state, MarketState::Voided as u8);
+        assert_eq!(processed_market.void_yes_bps, 4_000);
+    }
+}
@@ -1,9 +1,11 @@
-use super::{ResultAccount, SearchMarketAccount, SearchMarketInstruction};
-use crate::LAMPORTS_PER_TOKEN;
+use super::{
+    find_vault_address, ResultAccount, SearchMarketAccount, SearchMarketEvent,
+    SearchMarketInstruction,
+};
+use crate::{SearchMarketError, LAMPORTS_PER_TOKEN};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    clock::Clock,
     entrypoint::ProgramResult,
     instruction::{AccountMeta, Instruction},
     msg,
@@ -12,9 +14,9 @@ use solana_program::{
     pubkey::Pubkey,
     system_instruction::transfer,
     system_program,
-    sysvar::Sysvar,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn withdraw_instruction(
     program_id: &Pubkey,
     market_pubkey: &Pubkey,
@@ -27,16 +29,15 @@ pub fn withdraw_instruction(
     no_token_pubkey: &Pubkey,
     amount: u64,
 ) -> Result<Instruction, std::io::Error> {
-    let (mint_authority_key, _bump_seed) =
-        Pubkey::find_program_address(&[b"mint_authority"], program_id);
+    let (vault_key, _bump_seed) = find_vault_address(program_id, market_pubkey);
     let data = SearchMarketInstruction::Withdraw { amount }.try_to_vec()?;
     let accounts = vec![
         AccountMeta::new_readonly(*market_pubkey, false),
-        AccountMeta::new_readonly(*result_pubkey, false),
+        AccountMeta::new(*result_pubkey, false),
         AccountMeta::new(*withdraw_pubkey, true),
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
-        AccountMeta::new(mint_authority_key, false),
+        AccountMeta::new(vault_key, false),
         AccountMeta::new_readonly(*token_owner_pubkey, true),
         AccountMeta::new(*yes_mint_pubkey, false),
         AccountMeta::new(*yes_token_pubkey, false),
@@ -51,6 +52,9 @@ pub fn withdraw_instruction(
     })
 }
 
+/// Burns `amount` complete yes and no token sets and returns their collateral. A complete set is
+/// worth one token's collateral whatever the outcome, so this works at any point in the market.
+/// Winning tokens on their own are paid out by `redeem`.
 pub fn withdraw(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let market_account_info = next_account_info(account_info_iter)?;
@@ -58,118 +62,141 @@ pub fn withdraw(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> P
     let withdraw_account_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
     let spl_token_program_info = next_account_info(account_info_iter)?;
-    let mint_authority_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
     let token_owner_info = next_account_info(account_info_iter)?;
     let yes_mint_account_info = next_account_info(account_info_iter)?;
     let yes_token_account_info = next_account_info(account_info_iter)?;
     let no_mint_account_info = next_account_info(account_info_iter)?;
     let no_token_account_info = next_account_info(account_info_iter)?;
-    let clock = Clock::get()?;
 
     if *market_account_info.owner != *program_id {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
     }
     let market = SearchMarketAccount::try_from_slice(*market_account_info.data.borrow())?;
 
     if *result_account_info.owner != *program_id {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::AccountNotOwnedByProgram.into());
+    }
+    let mut result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
+    if result.search_market != *market_account_info.key {
+        return Err(SearchMarketError::ResultNotInMarket.into());
     }
-    let result = ResultAccount::try_from_slice(&result_account_info.data.borrow())?;
 
     if !withdraw_account_info.is_signer {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
     if *system_program_info.key != system_program::id() {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::InvalidSystemProgram.into());
     }
 
     if *spl_token_program_info.key != spl_token::id() {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::InvalidTokenProgram.into());
     }
 
-    if Pubkey::create_program_address(&[b"mint_authority", &[result.bump_seed]], program_id)?
-        != *mint_authority_info.key
-    {
-        return Err(ProgramError::InvalidAccountData);
+    let vault_seeds: &[&[u8]] = &[
+        b"vault",
+        &market_account_info.key.to_bytes(),
+        &[market.vault_bump_seed],
+    ];
+    if Pubkey::create_program_address(vault_seeds, program_id)? != *vault_account_info.key {
+        return Err(SearchMarketError::InvalidVault.into());
     }
 
     if *yes_mint_account_info.key != result.yes_mint {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::MintMismatch.into());
     }
 
     if *no_mint_account_info.key != result.no_mint {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SearchMarketError::MintMismatch.into());
     }
 
-    let withdraw_amount = amount * LAMPORTS_PER_TOKEN;
-    let mut yes_amount = 0;
-    let mut no_amount = 0;
-    if market.best_result == Pubkey::default() {
-        yes_amount = amount;
-        no_amount = amount;
-    } else if market.best_result == *result_account_info.key {
-        yes_amount = amount;
-    } else {
-        no_amount = amount;
+    let withdraw_amount = amount
+        .checked_mul(LAMPORTS_PER_TOKEN)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    if withdraw_amount > result.collateral {
+        msg!(
+            "Withdraw {} exceeds result collateral {}",
+            withdraw_amount,
+            result.collateral
+        );
+        return Err(SearchMarketError::InsufficientCollateral.into());
     }
 
-    msg!("transfer sol escrow to withdraw");
+    msg!("transfer sol vault to withdraw");
     invoke_signed(
         &transfer(
-            mint_authority_info.key,
+            vault_account_info.key,
             withdraw_account_info.key,
             withdraw_amount,
         ),
         &[
-            mint_authority_info.clone(),
+            vault_account_info.clone(),
             withdraw_account_info.clone(),
             system_program_info.clone(),
         ],
-        &[&[b"mint_authority", &[result.bump_seed]]],
+        &[vault_seeds],
     )?;
 
-    if yes_amount > 0 {
-        msg!("burn yes tokens");
-        invoke(
-            &spl_token::instruction::burn(
-                spl_token_program_info.key,
-                yes_token_account_info.key,
-                yes_mint_account_info.key,
-                token_owner_info.key,
-                &[],
-                yes_amount,
-            )?,
-            &[
-                yes_token_account_info.clone(),
-                yes_mint_account_info.clone(),
-                token_owner_info.clone(),
-                spl_token_program_info.clone(),
-            ],
-        )?;
-    }
+    msg!("burn yes tokens");
+    invoke(
+        &spl_token::instruction::burn(
+            spl_token_program_info.key,
+            yes_token_account_info.key,
+            yes_mint_account_info.key,
+            token_owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            yes_token_account_info.clone(),
+            yes_mint_account_info.clone(),
+            token_owner_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+    )?;
 
-    if no_amount > 0 {
-        msg!("burn no tokens");
-        invoke(
-            &spl_token::instruction::burn(
-                spl_token_program_info.key,
-                no_token_account_info.key,
-                no_mint_account_info.key,
-                token_owner_info.key,
-                &[],
-                amount,
-            )?,
-            &[
-                no_token_account_info.clone(),
-                no_mint_account_info.clone(),
-                token_owner_info.clone(),
-                spl_token_program_info.clone(),
-            ],
-        )?;
-    }
+    msg!("burn no tokens");
+    invoke(
+        &spl_token::instruction::burn(
+            spl_token_program_info.key,
+            no_token_account_info.key,
+            no_mint_account_info.key,
+            token_owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            no_token_account_info.clone(),
+            no_mint_account_info.clone(),
+            token_owner_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+    )?;
 
-    Ok(())
+    result.collateral = result
+        .collateral
+        .checked_sub(withdraw_amount)
+        .ok_or(SearchMarketError::ArithmeticOverflow)?;
+    result.serialize(&mut &mut result_account_info.data.borrow_mut()[..])?;
+
+    #[cfg(feature = "debug-invariants")]
+    super::check_result_backing(
+        &market,
+        result_account_info.key,
+        &result,
+        vault_account_info,
+        yes_mint_account_info,
+        no_mint_account_info,
+    )?;
+
+    SearchMarketEvent::Withdrawn {
+        market: *market_account_info.key,
+        result: *result_account_info.key,
+        owner: *token_owner_info.key,
+        amount,
+    }
+    .emit()
 }
 
 #[cfg(test)]
@@ -194,7 +221,6 @@ pub mod test {
         program_test: ProgramTest,
         decision_authority: Keypair,
         market_key: Pubkey,
-        market: SearchMarketAccount,
         result_key: Pubkey,
         result: ResultAccount,
         yes_token_pubkey: Pubkey,
@@ -210,10 +236,15 @@ pub mod test {
                 ProgramTest::new("askbid", program_id, processor!(process_instruction));
 
             let decision_authority = Keypair::new();
-            let market =
-                SearchMarketAccount::new(decision_authority.pubkey(), "cyberpunk".to_string(), 1);
+            let mut market = SearchMarketAccount::new(
+                decision_authority.pubkey(),
+                "cyberpunk".to_string(),
+                1,
+                10,
+                0,
+            );
             let (market_key, create_market) =
-                setup_market(&market, 1, &mut program_test, &program_id);
+                setup_market(&mut market, 1, &mut program_test, &program_id);
 
             let mut result = ResultAccount::new(
                 market_key,
@@ -222,10 +253,9 @@ pub mod test {
                 String::from("A game fated to be legend"),
                 Pubkey::new_unique(),
                 Pubkey::new_unique(),
-                0,
             );
             let (result_key, create_result) =
-                setup_result(&mut result, &mut program_test, &program_id);
+                setup_result(&mut result, 0, &decision_authority.pubkey(), &program_id);
 
             let deposit_keypair = Keypair::new();
             let (yes_token_pubkey, init_yes_token) = setup_token(
@@ -257,7 +287,6 @@ pub mod test {
                 program_test,
                 decision_authority,
                 market_key,
-                market,
                 result_key,
                 result,
                 yes_token_pubkey,
@@ -349,6 +378,14 @@ pub mod test {
             .unwrap();
         let no_token_data = Account::unpack_from_slice(&no_token_account.data).unwrap();
         assert_eq!(no_token_data.amount, 1);
+
+        let result_account = banks_client
+            .get_account(withdraw_test.result_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let result = ResultAccount::try_from_slice(&result_account.data).unwrap();
+        assert_eq!(result.collateral, LAMPORTS_PER_TOKEN);
     }
 
     #[tokio::test]
@@ -1,18 +1,153 @@
 use borsh::BorshDeserialize;
 use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg, pubkey::Pubkey,
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
 };
 use thiserror::Error;
 mod instructions;
-use instructions::*;
+pub use instructions::*;
 
 #[cfg(test)]
 mod test_utils;
 
-#[derive(Clone, Debug, Eq, Error, PartialEq)]
-pub enum SearchMarketError {}
+/// Errors surfaced to clients as `ProgramError::Custom(code)`. New variants must only ever be
+/// appended so that existing codes stay stable.
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub enum SearchMarketError {
+    #[error("Account not owned by program")]
+    AccountNotOwnedByProgram = 0,
+    #[error("Expires slot offset must be positive")]
+    InvalidExpiry,
+    #[error("Market already expired")]
+    MarketExpired,
+    #[error("Url did not parse")]
+    InvalidUrl,
+    #[error("Mint authority invalid")]
+    InvalidMintAuthority,
+    #[error("Rent sysvar account invalid")]
+    InvalidRentSysvar,
+    #[error("Token program account invalid")]
+    InvalidTokenProgram,
+    #[error("System program account invalid")]
+    InvalidSystemProgram,
+    #[error("Result does not belong to market")]
+    ResultNotInMarket,
+    #[error("Mint does not match result")]
+    MintMismatch,
+    #[error("Signer is not the market decision authority")]
+    WrongDecisionAuthority,
+    #[error("Escrow account does not match order")]
+    EscrowMismatch,
+    #[error("Buy price is below sell price")]
+    PriceNotCrossed,
+    #[error("Signer is not an execution authority of either order")]
+    WrongExecutionAuthority,
+    #[error("Unknown order side")]
+    InvalidOrderSide,
+    #[error("Result collateral too low to cover withdrawal")]
+    InsufficientCollateral,
+    #[error("Account does not match its program derived address")]
+    InvalidAccountAddress,
+    #[error("Account does not match the one recorded on the order")]
+    OrderAccountMismatch,
+    #[error("Signer is neither the order owner nor its execution authority")]
+    WrongOrderAuthority,
+    #[error("Orders are not for the same result")]
+    OrderResultMismatch,
+    #[error("Order book side is full")]
+    OrderBookFull,
+    #[error("Order is not resting in the book")]
+    OrderNotInBook,
+    #[error("Accounts for a crossing resting order were not supplied")]
+    MakerAccountsMissing,
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[error("Escrow or vault balance below what it must cover")]
+    InvariantViolated,
+    #[error("Tick size must evenly divide a token's collateral")]
+    InvalidTickSize,
+    #[error("Price outside the token's range or off the market's tick")]
+    InvalidPrice,
+    #[error("Bounty refund share above 10000 basis points")]
+    InvalidBountyShare,
+    #[error("Fee destination does not match market")]
+    FeeDestinationMismatch,
+    #[error("Market has not expired")]
+    MarketNotExpired,
+    #[error("Market bounty already paid out")]
+    BountyAlreadyPaid,
+    #[error("Fee rate above 10000 basis points")]
+    InvalidFeeRate,
+    #[error("Program is paused")]
+    ProgramPaused,
+    #[error("Signer is not the config admin")]
+    WrongAdmin,
+    #[error("String longer than the configured maximum")]
+    StringTooLong,
+    #[error("Market is not in a state that allows this")]
+    InvalidMarketState,
+    #[error("Trading on this market has closed")]
+    TradingClosed,
+    #[error("Decision can still be challenged")]
+    ChallengeWindowOpen,
+    #[error("Challenge window has closed")]
+    ChallengeWindowClosed,
+    #[error("Decision already challenged")]
+    AlreadyChallenged,
+    #[error("Signer is not the arbiter")]
+    WrongArbiter,
+    #[error("Market has not been finalized")]
+    MarketNotFinalized,
+    #[error("Decision signer set or threshold invalid")]
+    InvalidDecisionSigners,
+    #[error("Payout weights must cover distinct results and sum to 10000 basis points")]
+    InvalidPayoutWeights,
+    #[error("Rank schedule too long or above 10000 basis points")]
+    InvalidRankSchedule,
+    #[error("Ranking longer than the schedule or repeats a result")]
+    InvalidRanking,
+    #[error("Void payout split above 10000 basis points")]
+    InvalidVoidSplit,
+    #[error("Market still has open results")]
+    ResultsStillOpen,
+    #[error("Redeemable tokens outstanding and close grace period not over")]
+    TokensOutstanding,
+    #[error("Order book still has resting orders")]
+    OrdersResting,
+    #[error("Account is not the recorded rent payer")]
+    PayerMismatch,
+    #[error("Result payouts in this market can sum to more than one token")]
+    ResultsNotExclusive,
+    #[error("Conversion needs no tokens of every other result in the market")]
+    IncompleteNoSet,
+    #[error("Market maker subsidy must fund at least one result, and only with liquidity set")]
+    InvalidAmmSubsidy,
+    #[error("Result has no market maker")]
+    AmmNotFunded,
+    #[error("Market maker price moved past the trade's limit")]
+    SlippageExceeded,
+    #[error("Pool amounts must be positive and within the pool's reserves")]
+    InvalidPoolAmount,
+    #[error("Market vault account invalid")]
+    InvalidVault,
+    #[error("Buy token account must be an unfrozen SPL account of the order's mint")]
+    InvalidTokenAccount,
+    #[error("Order quantity below the minimum")]
+    OrderTooSmall,
+    #[error("Challenge bond must be positive")]
+    InvalidChallengeBond,
+    #[error("Arbiter can no longer rule on the challenge")]
+    ArbiterWindowClosed,
+}
+
+impl From<SearchMarketError> for ProgramError {
+    fn from(e: SearchMarketError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
 
 pub const LAMPORTS_PER_TOKEN: u64 = 100_000;
+pub const BASIS_POINTS: u64 = 10_000;
 
 solana_program::declare_id!("CtRJbPMscDFRJptvh6snF5GJXDNCJHMFsfYoczds37AV");
 entrypoint!(process_instruction);
@@ -23,18 +158,41 @@ pub fn process_instruction(
     input: &[u8],
 ) -> ProgramResult {
     let instruction = SearchMarketInstruction::try_from_slice(input)?;
-    msg!("Instruction: {:?}", instruction);
     match instruction {
         SearchMarketInstruction::CreateMarket {
-            expires_slot_offset,
             search_string,
-        } => create_market(program_id, accounts, expires_slot_offset, search_string),
+            nonce,
+            market_bump_seed,
+            vault_bump_seed,
+            params,
+        } => create_market(
+            program_id,
+            accounts,
+            search_string,
+            nonce,
+            market_bump_seed,
+            vault_bump_seed,
+            params,
+        ),
         SearchMarketInstruction::CreateResult {
             url,
             name,
             snippet,
-            bump_seed,
-        } => create_result(program_id, accounts, url, name, snippet, bump_seed),
+            result_bump_seed,
+            yes_mint_bump_seed,
+            no_mint_bump_seed,
+            book_bump_seed,
+        } => create_result(
+            program_id,
+            accounts,
+            url,
+            name,
+            snippet,
+            result_bump_seed,
+            yes_mint_bump_seed,
+            no_mint_bump_seed,
+            book_bump_seed,
+        ),
         SearchMarketInstruction::Deposit { amount } => deposit(program_id, accounts, amount),
         SearchMarketInstruction::Withdraw { amount } => withdraw(program_id, accounts, amount),
         SearchMarketInstruction::Decide => decide(program_id, accounts),
@@ -42,24 +200,112 @@ pub fn process_instruction(
             side,
             price,
             quantity,
+            client_id,
+            order_bump_seed,
             escrow_bump_seed,
+            referrer,
+            fee_vault_bump_seed,
         } => create_order(
             program_id,
             accounts,
             side,
             price,
             quantity,
+            client_id,
+            order_bump_seed,
             escrow_bump_seed,
+            referrer,
+            fee_vault_bump_seed,
         ),
         SearchMarketInstruction::FillOrder {
-            sol_escrow_bump_seed,
-            token_escrow_bump_seed,
+            side,
+            limit_price,
+            quantity,
+            referrer,
+            fee_vault_bump_seed,
         } => fill_order(
             program_id,
             accounts,
-            sol_escrow_bump_seed,
-            token_escrow_bump_seed,
+            side,
+            limit_price,
+            quantity,
+            referrer,
+            fee_vault_bump_seed,
         ),
         SearchMarketInstruction::CancelOrder => cancel_order(program_id, accounts),
+        SearchMarketInstruction::ExpireBounty => expire_bounty(program_id, accounts),
+        SearchMarketInstruction::ClaimFees {
+            fee_vault_bump_seed,
+        } => claim_fees(program_id, accounts, fee_vault_bump_seed),
+        SearchMarketInstruction::InitializeConfig {
+            params,
+            config_bump_seed,
+        } => initialize_config(program_id, accounts, params, config_bump_seed),
+        SearchMarketInstruction::UpdateConfig { params } => {
+            update_config(program_id, accounts, params)
+        }
+        SearchMarketInstruction::SetPaused { paused } => set_paused(program_id, accounts, paused),
+        SearchMarketInstruction::ProposeAdmin { new_admin } => {
+            propose_admin(program_id, accounts, new_admin)
+        }
+        SearchMarketInstruction::AcceptAdmin => accept_admin(program_id, accounts),
+        SearchMarketInstruction::ClaimProtocolFees {
+            fee_vault_bump_seed,
+        } => claim_protocol_fees(program_id, accounts, fee_vault_bump_seed),
+        SearchMarketInstruction::Redeem { amount } => redeem(program_id, accounts, amount),
+        SearchMarketInstruction::ChallengeDecision => challenge_decision(program_id, accounts),
+        SearchMarketInstruction::ResolveChallenge => resolve_challenge(program_id, accounts),
+        SearchMarketInstruction::FinalizeMarket => finalize_market(program_id, accounts),
+        SearchMarketInstruction::DelegateDecision {
+            delegate,
+            expires_slot,
+        } => delegate_decision(program_id, accounts, delegate, expires_slot),
+        SearchMarketInstruction::DecideWeighted { weights } => {
+            decide_weighted(program_id, accounts, weights)
+        }
+        SearchMarketInstruction::DecideRanked { count } => {
+            decide_ranked(program_id, accounts, count)
+        }
+        SearchMarketInstruction::VoidMarket => void_market(program_id, accounts),
+        SearchMarketInstruction::CloseResult => close_result(program_id, accounts),
+        SearchMarketInstruction::CloseMarket => close_market(program_id, accounts),
+        SearchMarketInstruction::ConvertNoToYes { amount } => {
+            convert_no_to_yes(program_id, accounts, amount)
+        }
+        SearchMarketInstruction::BuyFromAmm {
+            yes,
+            amount,
+            max_cost,
+        } => buy_from_amm(program_id, accounts, yes, amount, max_cost),
+        SearchMarketInstruction::SellToAmm {
+            yes,
+            amount,
+            min_proceeds,
+        } => sell_to_amm(program_id, accounts, yes, amount, min_proceeds),
+        SearchMarketInstruction::CreatePool {
+            pool_bump_seed,
+            pool_tokens_bump_seed,
+            lp_mint_bump_seed,
+        } => create_pool(
+            program_id,
+            accounts,
+            pool_bump_seed,
+            pool_tokens_bump_seed,
+            lp_mint_bump_seed,
+        ),
+        SearchMarketInstruction::AddLiquidity {
+            yes_amount,
+            max_sol,
+        } => add_liquidity(program_id, accounts, yes_amount, max_sol),
+        SearchMarketInstruction::RemoveLiquidity {
+            lp_amount,
+            min_yes,
+            min_sol,
+        } => remove_liquidity(program_id, accounts, lp_amount, min_yes, min_sol),
+        SearchMarketInstruction::Swap {
+            buy_yes,
+            amount_in,
+            min_out,
+        } => swap(program_id, accounts, buy_yes, amount_in, min_out),
     }
 }